@@ -0,0 +1,250 @@
+use crate::Command;
+use crate::state::{Activity, AppState};
+use anyhow::anyhow;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+const HISTORY_LINES: usize = 10;
+const OFFLINE: &str = ", its reporter is offline";
+/// Longer durations are refused, they come from chat messages and would overflow `Instant`
+const MAX_DURATION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+const HELP: &str = "Commands:
+status - connected reporters and warned characters
+ack <character> - stop warns of the character until the sighting ends, and acknowledge it on its reporter
+snooze <character> <duration> - silence the character on its reporter, e.g. snooze Alt A 30m
+mute <duration> - stop all messages, e.g. mute 10m
+unmute - send messages again
+history - recent events";
+
+/// `30s`, `10m`, `2h` or `1d`, up to a week
+fn parse_duration(text: &str) -> anyhow::Result<Duration> {
+	let unit_start = text.char_indices().last().map_or(0, |(i, _)| i);
+	let (number, unit) = text.split_at(unit_start);
+	let number: u64 = number
+		.parse()
+		.map_err(|_| anyhow!("invalid duration {text}, expect like 10m"))?;
+	let unit_secs = match unit {
+		"s" => 1,
+		"m" => 60,
+		"h" => 60 * 60,
+		"d" => 60 * 60 * 24,
+		_ => return Err(anyhow!("invalid duration {text}, expect like 10m")),
+	};
+	number
+		.checked_mul(unit_secs)
+		.map(Duration::from_secs)
+		.filter(|duration| *duration <= MAX_DURATION)
+		.ok_or_else(|| anyhow!("duration {text} is longer than a week"))
+}
+
+fn format_ago(elapsed: Duration) -> String {
+	match elapsed.as_secs() {
+		secs @ 0..60 => format!("{secs}s ago"),
+		secs @ 60..3600 => format!("{}m ago", secs / 60),
+		secs => format!("{}h ago", secs / 3600),
+	}
+}
+
+fn status(activity: &Activity, now: Instant) -> String {
+	let mut reply = "Reporters:".to_string();
+	for (name, state) in &activity.reporters {
+		let connection = match state.disconnected_at {
+			_ if state.connections > 0 => "connected".to_string(),
+			Some(at) => format!("disconnected {}", format_ago(now.duration_since(at))),
+			None => "never connected".to_string(),
+		};
+		let _ = write!(reply, "\n{name}: {connection}");
+	}
+	if let Some(until) = activity.muted_until.filter(|until| now < *until) {
+		let _ = write!(
+			reply,
+			"\nMuted for {}s",
+			until.duration_since(now).as_secs()
+		);
+	}
+	if !activity.characters.is_empty() {
+		reply.push_str("\nCharacters:");
+	}
+	for (character, state) in &activity.characters {
+		let condition = match (state.warning(now), state.acked) {
+			(true, true) => "warning (acked)",
+			(true, false) => "warning",
+			(false, _) => "clear",
+		};
+		let last_event = state
+			.last_event
+			.map(|at| format_ago(now.duration_since(at)))
+			.unwrap_or_default();
+		let _ = write!(
+			reply,
+			"\n[{}] {character}: {condition}, last event {last_event}",
+			state.reporter
+		);
+	}
+	reply
+}
+
+fn history(activity: &Activity, now: Instant) -> String {
+	if activity.history.is_empty() {
+		return "No events yet".to_string();
+	}
+	activity
+		.history
+		.iter()
+		.rev()
+		.take(HISTORY_LINES)
+		.map(|entry| {
+			format!(
+				"{} [{}] {} {}",
+				format_ago(now.duration_since(entry.at)),
+				entry.reporter,
+				entry.kind,
+				entry.character
+			)
+		})
+		.collect::<Vec<_>>()
+		.join("\n")
+}
+
+/// Reply to a QQ message, `None` when the message is not a command
+pub fn handle(state: &AppState, text: &str) -> Option<String> {
+	let now = Instant::now();
+	let text = text.trim();
+	let text = text.strip_prefix('/').unwrap_or(text);
+	let (name, arg) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+	let arg = arg.trim();
+	let mut activity = state.activity.lock().unwrap();
+	let reply = match name.to_lowercase().as_str() {
+		"help" => HELP.to_string(),
+		"status" => status(&activity, now),
+		"history" => history(&activity, now),
+		"ack" if arg.is_empty() => "Usage: ack <character>".to_string(),
+		"ack" => {
+			let forwarded = activity.forward(arg, |title| Command::Acknowledge { title: Some(title) });
+			match forwarded {
+				None => format!("Unknown character {arg}"),
+				Some(delivered) => {
+					let mut reply = if activity.ack(arg, now) {
+						format!("Acknowledged {arg}")
+					} else {
+						format!("Acknowledged {arg}, it has no ongoing warn")
+					};
+					if !delivered {
+						reply.push_str(OFFLINE);
+					}
+					reply
+				}
+			}
+		}
+		"snooze" => match arg.rsplit_once(char::is_whitespace) {
+			None => "Usage: snooze <character> <duration>".to_string(),
+			Some((character, duration_text)) => match parse_duration(duration_text) {
+				Ok(duration) => {
+					let character = character.trim();
+					let minutes = duration.as_secs().div_ceil(60);
+					let forwarded = activity.forward(character, |title| Command::Snooze {
+						title: Some(title),
+						minutes,
+					});
+					match forwarded {
+						None => format!("Unknown character {character}"),
+						Some(true) => format!("Snoozed {character} for {minutes}m"),
+						Some(false) => format!("Not snoozed {character}{OFFLINE}"),
+					}
+				}
+				Err(e) => e.to_string(),
+			},
+		},
+		"mute" => match parse_duration(arg) {
+			Ok(duration) => {
+				// parse_duration caps the duration far below an overflow
+				activity.muted_until = now.checked_add(duration);
+				format!("Muted for {arg}")
+			}
+			Err(e) => e.to_string(),
+		},
+		"unmute" => {
+			activity.muted_until = None;
+			"Unmuted".to_string()
+		}
+		_ => return None,
+	};
+	Some(reply)
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::Command;
+	use crate::command::{handle, parse_duration};
+	use crate::config::tests::example;
+	use crate::state::AppState;
+	use std::time::Duration;
+	use tokio::sync::mpsc;
+
+	#[test]
+	fn commands() {
+		assert_eq!(parse_duration("10m").unwrap(), Duration::from_secs(600));
+		assert!(parse_duration("10").is_err());
+		assert!(parse_duration("").is_err());
+		assert!(parse_duration("10分").is_err());
+		assert!(parse_duration("18446744073709551615s").is_err());
+		assert!(parse_duration("18446744073709551615d").is_err());
+		assert!(parse_duration("8d").is_err());
+
+		let state = AppState::new(example());
+		assert!(state.accept("Alice", "EVE - Alt A", "Warn"));
+		let (outbox, mut commands) = mpsc::unbounded_channel();
+		state.connect("Alice", outbox);
+		assert_eq!(handle(&state, "hello"), None);
+		assert_eq!(handle(&state, "/ack Alt A").unwrap(), "Acknowledged Alt A");
+		assert_eq!(
+			commands.try_recv().unwrap(),
+			Command::Acknowledge {
+				title: Some("EVE - Alt A".to_string())
+			}
+		);
+		assert_eq!(
+			handle(&state, "snooze Alt A 90s").unwrap(),
+			"Snoozed Alt A for 2m"
+		);
+		assert_eq!(
+			commands.try_recv().unwrap(),
+			Command::Snooze {
+				title: Some("EVE - Alt A".to_string()),
+				minutes: 2
+			}
+		);
+		assert_eq!(
+			handle(&state, "ack Alt C").unwrap(),
+			"Unknown character Alt C"
+		);
+		assert!(
+			handle(&state, "status")
+				.unwrap()
+				.contains("[Alice] Alt A: warning (acked)")
+		);
+		assert!(
+			handle(&state, "status")
+				.unwrap()
+				.contains("Alice: connected")
+		);
+		assert!(
+			handle(&state, "status")
+				.unwrap()
+				.contains("Bob: never connected")
+		);
+		assert_eq!(
+			handle(&state, "mute 18446744073709551615s").unwrap(),
+			"duration 18446744073709551615s is longer than a week"
+		);
+		assert_eq!(handle(&state, "mute 10m").unwrap(), "Muted for 10m");
+		assert!(!state.accept("Bob", "EVE - Alt B", "Reminder"));
+		assert_eq!(
+			handle(&state, "ack Alt B").unwrap(),
+			"Acknowledged Alt B, it has no ongoing warn, its reporter is offline"
+		);
+		let history = handle(&state, "history").unwrap();
+		assert!(history.contains("[Alice] Warn Alt A"));
+		assert!(!history.contains("Alt B"));
+	}
+}
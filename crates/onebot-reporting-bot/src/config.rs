@@ -0,0 +1,327 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+use subtle::ConstantTimeEq;
+
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OnebotConfig {
+	/// Websocket url of the OneBot implementation, `ws://` or `wss://`
+	pub url: String,
+	#[serde(default, skip_serializing)]
+	pub key: Option<String>,
+}
+
+/// A player's reporter, it sends `Authorization: Bearer <token>` or connects to `/ws?token=<token>`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Reporter {
+	/// Prefixes the messages of events this reporter sends
+	pub name: String,
+	#[serde(skip_serializing)]
+	pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type")]
+pub enum Recipient {
+	Private {
+		id: i64,
+		/// Characters whose events are sent here, empty means all
+		#[serde(default)]
+		characters: Vec<String>,
+	},
+	Group {
+		id: i64,
+		#[serde(default)]
+		characters: Vec<String>,
+		/// Members mentioned on warns
+		#[serde(default)]
+		warn_mentions: Vec<i64>,
+	},
+}
+
+impl Recipient {
+	pub fn id(&self) -> i64 {
+		match self {
+			Self::Private { id, .. } | Self::Group { id, .. } => *id,
+		}
+	}
+
+	/// `None` are messages not about a character, every recipient gets them
+	pub fn accepts(&self, character: Option<&str>) -> bool {
+		let (Self::Private { characters, .. } | Self::Group { characters, .. }) = self;
+		let Some(character) = character else {
+			return true;
+		};
+		characters.is_empty() || characters.iter().any(|name| name == character)
+	}
+
+	pub fn warn_mentions(&self) -> &[i64] {
+		match self {
+			Self::Private { .. } => &[],
+			Self::Group { warn_mentions, .. } => warn_mentions,
+		}
+	}
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Config {
+	#[serde(default = "default_listen")]
+	pub listen: SocketAddr,
+	pub onebot: OnebotConfig,
+	pub reporters: Vec<Reporter>,
+	/// Recipients are alerted when a reporter stays disconnected this long
+	#[serde(
+		default = "default_disconnect_grace",
+		deserialize_with = "deserialize_duration"
+	)]
+	pub disconnect_grace: Duration,
+	pub recipients: Vec<Recipient>,
+	/// Warns of a character arriving within this span after its last sent warn are dropped
+	#[serde(
+		default = "default_warn_spacing",
+		deserialize_with = "deserialize_duration"
+	)]
+	pub warn_spacing: Duration,
+	/// Same as `warn_spacing`, for reminders
+	#[serde(
+		default = "default_reminder_spacing",
+		deserialize_with = "deserialize_duration"
+	)]
+	pub reminder_spacing: Duration,
+	/// QQ ids allowed to send commands to the bot, empty disables commands
+	#[serde(default)]
+	pub admins: Vec<i64>,
+	/// Secret of the OneBot HTTP POST reporting to `/onebot`, checked against its `X-Signature`
+	#[serde(default, skip_serializing)]
+	pub post_secret: Option<String>,
+	/// Larger snapshots are sent without the image, 0 disables snapshots
+	#[serde(default = "default_snapshot_max_bytes")]
+	pub snapshot_max_bytes: usize,
+}
+
+/// Seconds, such as `60`, or the `{ secs, nanos }` table of a serialized `Duration`
+fn deserialize_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+	#[derive(Deserialize)]
+	#[serde(untagged)]
+	enum Seconds {
+		Secs(u64),
+		Table { secs: u64, nanos: u32 },
+	}
+	Ok(match Seconds::deserialize(deserializer)? {
+		Seconds::Secs(secs) => Duration::from_secs(secs),
+		Seconds::Table { secs, nanos } => Duration::new(secs, nanos),
+	})
+}
+
+/// Values of config.example.toml, such as `<YOUR SECRET>`, left in place
+fn is_placeholder(value: &str) -> bool {
+	value.starts_with('<') && value.ends_with('>')
+}
+
+fn default_listen() -> SocketAddr {
+	SocketAddr::from(([0, 0, 0, 0], 8080))
+}
+
+fn default_disconnect_grace() -> Duration {
+	Duration::from_secs(60)
+}
+
+fn default_warn_spacing() -> Duration {
+	Duration::from_secs(60)
+}
+
+fn default_reminder_spacing() -> Duration {
+	Duration::from_secs(300)
+}
+
+fn default_snapshot_max_bytes() -> usize {
+	1024 * 1024
+}
+
+impl Config {
+	/// Reads the file at `BOT_CONFIG`, or `config.toml` in the working directory
+	pub fn load() -> anyhow::Result<Self> {
+		let path = std::env::var("BOT_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
+		Self::read(Path::new(&path))
+	}
+
+	/// Tokens are compared in constant time, only their length leaks
+	pub fn reporter(&self, token: &str) -> Option<&Reporter> {
+		self
+			.reporters
+			.iter()
+			.find(|reporter| bool::from(reporter.token.as_bytes().ct_eq(token.as_bytes())))
+	}
+
+	pub fn read(path: &Path) -> anyhow::Result<Self> {
+		println!("Reading config from {path:?}");
+		let config_str =
+			std::fs::read_to_string(path).map_err(|e| anyhow!("read config {path:?} failed: {e}"))?;
+		config_str.parse()
+	}
+
+	fn validate(&self) -> anyhow::Result<()> {
+		if !self.onebot.url.starts_with("ws://") && !self.onebot.url.starts_with("wss://") {
+			return Err(anyhow!(
+				"onebot.url must start with ws:// or wss://, got {}",
+				self.onebot.url
+			));
+		}
+		if self.reporters.is_empty() {
+			return Err(anyhow!("There is no reporter"));
+		}
+		let mut names = HashSet::new();
+		let mut tokens = HashSet::new();
+		for reporter in &self.reporters {
+			if reporter.token.is_empty() || is_placeholder(&reporter.token) {
+				return Err(anyhow!("token of reporter {} is not set", reporter.name));
+			}
+			if !names.insert(&reporter.name) || !tokens.insert(&reporter.token) {
+				return Err(anyhow!(
+					"reporter {} shares its name or token with another",
+					reporter.name
+				));
+			}
+		}
+		if self.recipients.is_empty() {
+			return Err(anyhow!("There is no recipient"));
+		}
+		for recipient in &self.recipients {
+			if recipient.id() <= 0 {
+				return Err(anyhow!("invalid recipient id {}", recipient.id()));
+			}
+		}
+		// without a signature anyone reaching `/onebot` could pose as an admin
+		if !self.admins.is_empty() && self.post_secret.as_deref().is_none_or(str::is_empty) {
+			return Err(anyhow!("post_secret is required when there are admins"));
+		}
+		if self.post_secret.as_deref().is_some_and(is_placeholder) {
+			return Err(anyhow!("post_secret is not set"));
+		}
+		if self.onebot.key.as_deref().is_some_and(is_placeholder) {
+			return Err(anyhow!(
+				"onebot.key is not set, leave it out when there is none"
+			));
+		}
+		Ok(())
+	}
+}
+
+impl FromStr for Config {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let config: Self = toml::from_str(s)?;
+		config.validate()?;
+		Ok(config)
+	}
+}
+
+#[cfg(test)]
+pub mod tests {
+	use crate::config::Config;
+	use std::time::Duration;
+
+	/// config.example.toml with its placeholders filled in
+	pub fn example() -> Config {
+		include_str!("../config.example.toml")
+			.replace(['<', '>'], "")
+			.parse()
+			.unwrap()
+	}
+
+	#[test]
+	fn example_config() {
+		assert!(
+			include_str!("../config.example.toml")
+				.parse::<Config>()
+				.is_err()
+		);
+		let config = example();
+		assert_eq!(config.listen.port(), 8080);
+		assert_eq!(config.warn_spacing, Duration::from_secs(60));
+		assert_eq!(config.reminder_spacing, Duration::from_secs(300));
+		assert_eq!(config.admins, [10001]);
+		let routed: Vec<_> = config
+			.recipients
+			.iter()
+			.filter(|recipient| recipient.accepts(Some("Alt B")))
+			.map(|recipient| recipient.id())
+			.collect();
+		assert_eq!(routed, [10001, 20002]);
+		assert_eq!(config.recipients[1].warn_mentions(), [10001, 10003]);
+		assert!(!config.recipients[1].accepts(Some("Alt B")));
+		assert!(config.recipients[1].accepts(None));
+		assert_eq!(config.disconnect_grace, Duration::from_secs(60));
+		assert_eq!(config.reporter("BOB TOKEN").unwrap().name, "Bob");
+		assert!(config.reporter("").is_none());
+		assert!(config.reporter("BOB TOKEM").is_none());
+
+		let bad_url = r#"
+			[[reporters]]
+			name = "Alice"
+			token = "a"
+			[[recipients]]
+			type = "Private"
+			id = 10001
+			[onebot]
+			url = "http://127.0.0.1:3001"
+		"#;
+		assert!(bad_url.parse::<Config>().is_err());
+		let missing_recipients = r#"
+			[[reporters]]
+			name = "Alice"
+			token = "a"
+			[onebot]
+			url = "ws://127.0.0.1:3001"
+		"#;
+		assert!(missing_recipients.parse::<Config>().is_err());
+		let shared_token = r#"
+			[[reporters]]
+			name = "Alice"
+			token = "a"
+			[[reporters]]
+			name = "Bob"
+			token = "a"
+			[[recipients]]
+			type = "Private"
+			id = 10001
+			[onebot]
+			url = "ws://127.0.0.1:3001"
+		"#;
+		assert!(shared_token.parse::<Config>().is_err());
+		let unsigned_admins = r#"
+			admins = [10001]
+			[[reporters]]
+			name = "Alice"
+			token = "a"
+			[[recipients]]
+			type = "Private"
+			id = 10001
+			[onebot]
+			url = "ws://127.0.0.1:3001"
+		"#;
+		assert!(unsigned_admins.parse::<Config>().is_err());
+		let table_duration = r#"
+			[[reporters]]
+			name = "Alice"
+			token = "a"
+			[[recipients]]
+			type = "Private"
+			id = 10001
+			[onebot]
+			url = "ws://127.0.0.1:3001"
+			[warn_spacing]
+			secs = 90
+			nanos = 0
+		"#;
+		let config: Config = table_duration.parse().unwrap();
+		assert_eq!(config.warn_spacing, Duration::from_secs(90));
+	}
+}
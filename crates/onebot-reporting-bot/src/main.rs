@@ -1,24 +1,112 @@
+mod command;
+mod config;
+mod state;
+
+use crate::config::{Config, OnebotConfig, Recipient};
+use crate::state::{AppState, character};
+use axum::Json;
+use axum::body::Bytes;
 use axum::extract::WebSocketUpgrade;
 use axum::extract::ws::Message;
 use axum::extract::ws::WebSocket;
-use axum::response::IntoResponse;
-use axum::routing::get;
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use hmac::{Hmac, Mac};
 use onebot_api::api::APISender;
 use onebot_api::communication::Client;
 use onebot_api::communication::ws::WsService;
 use onebot_api::message::segment_builder::SegmentBuilder;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha1::Sha1;
+use std::collections::HashMap;
 use std::ops::Deref;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
-use tokio::sync::OnceCell;
+use tokio::sync::{OnceCell, mpsc};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum Event {
-	Warn { title: String },
-	Reminder { title: String },
+	Warn {
+		title: String,
+	},
+	/// Follows the first warn of a sighting once the reporter saved its snapshot
+	Snapshot {
+		title: String,
+		snapshot: Snapshot,
+	},
+	Reminder {
+		title: String,
+	},
+}
+
+/// Control commands of the reporter, forwarded from QQ over its websocket
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Command {
+	Acknowledge { title: Option<String> },
+	Snooze { title: Option<String>, minutes: u64 },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+	/// Path under the reporter's snapshot dir, its sse server serves the file at `/snapshots/<name>`
+	#[serde(default)]
+	pub name: String,
+	/// Base64 encoded png, absent when the reporter has `thumbnail_width = 0` in its `[snapshot]`
+	#[serde(default)]
+	pub thumbnail: Option<String>,
+}
+
+/// Turns the snapshot thumbnail into an image segment file, only png and jpeg under `max_bytes` are sent
+fn snapshot_image(snapshot: Snapshot, max_bytes: usize) -> Option<String> {
+	let thumbnail = snapshot.thumbnail?;
+	if !thumbnail.starts_with("iVBORw0KGgo") && !thumbnail.starts_with("/9j/") {
+		println!("Snapshot skipped: not a png or jpeg image");
+		return None;
+	}
+	if thumbnail.len() / 4 * 3 > max_bytes {
+		println!("Snapshot skipped: larger than {max_bytes} bytes");
+		return None;
+	}
+	Some(format!("base64://{thumbnail}"))
+}
+
+/// Sends the text to every recipient routed for the character, or to all for `None`, warns mention the configured members
+async fn notify(
+	config: &Config,
+	character: Option<&str>,
+	text: String,
+	image: Option<String>,
+	warn: bool,
+) {
+	let Ok(client) = OnebotClient::instance() else {
+		return;
+	};
+	for recipient in config
+		.recipients
+		.iter()
+		.filter(|recipient| recipient.accepts(character))
+	{
+		let mut builder = SegmentBuilder::new();
+		if warn {
+			for member in recipient.warn_mentions() {
+				builder = builder.at(member.to_string()).text(" ");
+			}
+		}
+		builder = builder.text(text.clone());
+		if let Some(image) = &image {
+			builder = builder.image(image.clone());
+		}
+		let msg = builder.build();
+		let _ = match recipient {
+			Recipient::Private { id, .. } => client.send_private_msg(*id, msg, None).await,
+			Recipient::Group { id, .. } => client.send_group_msg(*id, msg, None).await,
+		};
+	}
 }
 
 pub struct OnebotClient {
@@ -32,56 +120,141 @@ impl Deref for OnebotClient {
 	}
 }
 
+static INSTANCE: OnceCell<OnebotClient> = OnceCell::const_new();
+
 impl OnebotClient {
-	pub async fn instance() -> anyhow::Result<&'static Self> {
-		static INSTANCE: OnceCell<OnebotClient> = OnceCell::const_new();
+	pub async fn connect(config: &OnebotConfig) -> anyhow::Result<&'static Self> {
 		INSTANCE
 			.get_or_try_init(async || {
-				let url = std::env::var("ONEBOT_URL")?;
-				let key = std::env::var("ONEBOT_KEY")?;
-				let key = if key.is_empty() { None } else { Some(key) };
-				let ws_service = WsService::new_with_token(url, key)?;
+				let key = config.key.clone().filter(|key| !key.is_empty());
+				let ws_service = WsService::new_with_token(config.url.clone(), key)?;
 				let client = Client::with_service(Box::new(ws_service), Some(Duration::from_secs(5)));
 				client.start_service().await?;
 				Ok(Self { client })
 			})
 			.await
 	}
+
+	pub fn instance() -> anyhow::Result<&'static Self> {
+		INSTANCE
+			.get()
+			.ok_or_else(|| anyhow::anyhow!("OneBot client is not connected"))
+	}
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-	OnebotClient::instance().await?;
-	let listener = tokio::net::TcpListener::bind("0.0.0.0:8080").await?;
+	let config = Config::load()?;
+	OnebotClient::connect(&config.onebot).await?;
+	let listener = tokio::net::TcpListener::bind(config.listen).await?;
+	println!("Listening on {}", config.listen);
 
-	let router = axum::Router::new().route("/ws", get(ws_handler));
+	let router = axum::Router::new()
+		.route("/ws", get(ws_handler))
+		.route("/onebot", post(onebot_handler))
+		.with_state(Arc::new(AppState::new(config)));
 
 	axum::serve(listener, router).await?;
 
 	Ok(())
 }
 
-async fn ws_handler(ws: WebSocketUpgrade) -> impl IntoResponse {
-	ws.on_upgrade(ws_processer)
+/// Message event of the OneBot HTTP POST reporting, other posts only fill `post_type`
+#[derive(Debug, Deserialize)]
+struct OnebotPost {
+	post_type: String,
+	#[serde(default)]
+	user_id: i64,
+	#[serde(default)]
+	raw_message: String,
 }
 
-async fn ws_processer(mut socket: WebSocket) {
-	println!("WebSocket client connected");
+/// OneBot signs the body with HMAC-SHA1 as `X-Signature: sha1=<hex>`
+fn verify_signature(secret: &str, headers: &HeaderMap, body: &[u8]) -> bool {
+	let Some(hex) = headers
+		.get("x-signature")
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.strip_prefix("sha1="))
+	else {
+		return false;
+	};
+	let Some(signature) = (0..hex.len())
+		.step_by(2)
+		.map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+		.collect::<Option<Vec<u8>>>()
+	else {
+		return false;
+	};
+	let mut mac = Hmac::<Sha1>::new_from_slice(secret.as_bytes()).expect("hmac takes any key size");
+	mac.update(body);
+	mac.verify_slice(&signature).is_ok()
+}
 
-	let user_id: i64 = std::env::var("MESSAGE_TARGET")
-		.expect("MESSAGE_TARGET must be set")
-		.parse()
-		.expect("MESSAGE_TARGET must be a valid integer");
+/// Commands of admins are answered with a quick reply in the same chat, posts are signed whenever there are admins
+async fn onebot_handler(
+	State(state): State<Arc<AppState>>,
+	headers: HeaderMap,
+	body: Bytes,
+) -> Response {
+	if let Some(secret) = &state.config.post_secret
+		&& !verify_signature(secret, &headers, &body)
+	{
+		return StatusCode::UNAUTHORIZED.into_response();
+	}
+	let Ok(post) = serde_json::from_slice::<OnebotPost>(&body) else {
+		return StatusCode::BAD_REQUEST.into_response();
+	};
+	if post.post_type != "message" || !state.config.admins.contains(&post.user_id) {
+		return StatusCode::NO_CONTENT.into_response();
+	}
+	match command::handle(&state, &post.raw_message) {
+		Some(reply) => Json(json!({ "reply": reply, "auto_escape": true })).into_response(),
+		None => StatusCode::NO_CONTENT.into_response(),
+	}
+}
+
+/// Reporters identify themselves by their token, as a bearer token or the `token` query parameter
+async fn ws_handler(
+	State(state): State<Arc<AppState>>,
+	Query(query): Query<HashMap<String, String>>,
+	headers: HeaderMap,
+	ws: WebSocketUpgrade,
+) -> Response {
+	let token = headers
+		.get(header::AUTHORIZATION)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.strip_prefix("Bearer "))
+		.or(query.get("token").map(String::as_str));
+	let Some(reporter) = token.and_then(|token| state.config.reporter(token)) else {
+		println!("WebSocket client rejected: unknown token");
+		return StatusCode::UNAUTHORIZED.into_response();
+	};
+	let name = reporter.name.clone();
+	ws.on_upgrade(move |socket| ws_processer(socket, state, name))
+}
 
-	let warn_spacing: u64 = std::env::var("WARN_SPACING")
-		.expect("WARN_SPACING must be set")
-		.parse()
-		.expect("WARN_SPACING must be a valid integer");
-	let warn_spacing = Duration::from_secs(warn_spacing);
+/// Alerts the recipients when the reporter is still gone after the grace
+async fn watch_disconnect(state: Arc<AppState>, name: String) {
+	let Some(since) = state.disconnect(&name) else {
+		return;
+	};
+	tokio::time::sleep(state.config.disconnect_grace).await;
+	if state.disconnect_alert_due(&name, since) {
+		let text = format!("Reporter {name} disconnected");
+		notify(&state.config, None, text, None, true).await;
+	}
+}
 
-	let warning = Arc::new(AtomicBool::new(false));
+async fn ws_processer(mut socket: WebSocket, state: Arc<AppState>, name: String) {
+	println!("WebSocket client {name} connected");
+	let (outbox, mut commands) = mpsc::unbounded_channel();
+	if state.connect(&name, outbox) {
+		let text = format!("Reporter {name} reconnected");
+		notify(&state.config, None, text, None, false).await;
+	}
 
 	tokio::spawn(async move {
+		let config = &state.config;
 		loop {
 			tokio::select! {
 				msg = socket.recv() => {
@@ -90,44 +263,58 @@ async fn ws_processer(mut socket: WebSocket) {
 							if let Ok(event) = serde_json::from_str::<Event>(&text) {
 								match event {
 									Event::Warn { title } => {
-										if warning.load(Ordering::Relaxed) {
+										let character = character(&title);
+										if !state.accept(&name, &title, "Warn") {
 											continue;
 										}
-										warning.store(true, Ordering::Relaxed);
-										let warning_clone = Arc::clone(&warning);
-										tokio::spawn(async move {
-											tokio::time::sleep(warn_spacing).await;
-											warning_clone.store(false, Ordering::Relaxed);
-										});
-
-										let msg = SegmentBuilder::new().text(format!("Warn {title}")).build();
-										if let Ok(client) = OnebotClient::instance().await {
-											let _ = client.send_private_msg(user_id, msg, None).await;
+										let text = format!("[{name}] Warn {title}");
+										notify(config, Some(character), text, None, true).await;
+									}
+									// the snapshot follows the warn, without its image it would repeat the warn
+									Event::Snapshot { title, snapshot } => {
+										let character = character(&title);
+										let Some(image) = snapshot_image(snapshot, config.snapshot_max_bytes) else {
+											continue;
+										};
+										if !state.accept(&name, &title, "Snapshot") {
+											continue;
 										}
+										let text = format!("[{name}] Warn {title}");
+										notify(config, Some(character), text, Some(image), false).await;
 									}
 									Event::Reminder { title } => {
-										let msg = SegmentBuilder::new()
-											.text(format!("Reminder {title}"))
-											.build();
-										if let Ok(client) = OnebotClient::instance().await {
-											let _ = client.send_private_msg(user_id, msg, None).await;
+										let character = character(&title);
+										if !state.accept(&name, &title, "Reminder") {
+											continue;
 										}
+										let text = format!("[{name}] Reminder {title}");
+										notify(config, Some(character), text, None, false).await;
 									}
 								}
 							}
 						}
 						Some(Ok(Message::Close(_))) | None => {
-							println!("WebSocket client disconnected");
+							println!("WebSocket client {name} disconnected");
 							break;
 						}
 						Some(Err(e)) => {
-							eprintln!("WebSocket error: {}", e);
+							eprintln!("WebSocket error of {name}: {}", e);
 							break;
 						}
 						_ => {} // 忽略 Ping/Pong 等，axum 通常自动处理
 					}
 				}
+				Some(command) = commands.recv() => {
+					println!("Forward {command:?} to {name}");
+					let text = serde_json::to_string(&command).unwrap();
+					if socket.send(Message::Text(text.into())).await.is_err() {
+						break;
+					}
+				}
 			}
 		}
+		// closes the outbox, so disconnect drops it
+		drop(commands);
+		watch_disconnect(state, name).await;
 	});
 }
@@ -0,0 +1,321 @@
+use crate::Command;
+use crate::config::Config;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Warns closer than this belong to the same sighting, an ack lasts until the sighting ends
+pub const WARN_RUN_GAP: Duration = Duration::from_secs(30);
+const HISTORY_SIZE: usize = 50;
+
+/// Reporter titles are `EVE - <character>`
+pub fn character(title: &str) -> &str {
+	title.strip_prefix("EVE - ").unwrap_or(title).trim()
+}
+
+/// Lets one event through per character and kind every spacing
+#[derive(Default)]
+pub struct Cooldown {
+	last_sent: HashMap<(String, &'static str), Instant>,
+}
+
+impl Cooldown {
+	pub fn allow(&mut self, character: &str, kind: &'static str, spacing: Duration) -> bool {
+		let now = Instant::now();
+		let key = (character.to_string(), kind);
+		match self.last_sent.get(&key) {
+			Some(last_sent) if now.duration_since(*last_sent) < spacing => false,
+			_ => {
+				self.last_sent.insert(key, now);
+				true
+			}
+		}
+	}
+}
+
+#[derive(Debug, Default)]
+pub struct ReporterState {
+	pub connections: usize,
+	pub disconnected_at: Option<Instant>,
+	/// A disconnect alert was sent, recipients are told when the reporter is back
+	pub alerted: bool,
+	/// Commands to the reporter, one per open connection
+	pub outboxes: Vec<UnboundedSender<Command>>,
+}
+
+#[derive(Debug, Default)]
+pub struct CharacterState {
+	/// Name of the reporter that last sent an event of the character
+	pub reporter: String,
+	/// Window title the reporter knows the character by
+	pub title: String,
+	pub last_warn: Option<Instant>,
+	pub last_event: Option<Instant>,
+	pub acked: bool,
+}
+
+impl CharacterState {
+	pub fn warning(&self, now: Instant) -> bool {
+		self
+			.last_warn
+			.is_some_and(|last_warn| now.duration_since(last_warn) < WARN_RUN_GAP)
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+	pub at: Instant,
+	pub kind: &'static str,
+	pub reporter: String,
+	pub character: String,
+}
+
+/// What the reporters sent recently, answered to QQ commands
+#[derive(Default)]
+pub struct Activity {
+	pub reporters: BTreeMap<String, ReporterState>,
+	pub characters: BTreeMap<String, CharacterState>,
+	pub history: VecDeque<HistoryEntry>,
+	pub muted_until: Option<Instant>,
+}
+
+impl Activity {
+	pub fn record(&mut self, reporter: &str, title: &str, kind: &'static str, now: Instant) {
+		let state = self
+			.characters
+			.entry(character(title).to_string())
+			.or_default();
+		state.reporter = reporter.to_string();
+		state.title = title.to_string();
+		if kind == "Warn" {
+			// a new sighting needs a new ack
+			if !state.warning(now) {
+				state.acked = false;
+			}
+			state.last_warn = Some(now);
+		}
+		state.last_event = Some(now);
+	}
+
+	/// Only sent events are kept, the reporters repeat a warn on every captured frame
+	fn push_history(&mut self, reporter: &str, character: &str, kind: &'static str, now: Instant) {
+		if self.history.len() >= HISTORY_SIZE {
+			self.history.pop_front();
+		}
+		self.history.push_back(HistoryEntry {
+			at: now,
+			kind,
+			reporter: reporter.to_string(),
+			character: character.to_string(),
+		});
+	}
+
+	pub fn muted(&self, now: Instant) -> bool {
+		self.muted_until.is_some_and(|until| now < until)
+	}
+
+	/// Muted events, and warns and snapshots of acknowledged sightings are not sent
+	pub fn silenced(&self, character: &str, kind: &'static str, now: Instant) -> bool {
+		if self.muted(now) {
+			return true;
+		}
+		matches!(kind, "Warn" | "Snapshot")
+			&& self
+				.characters
+				.get(character)
+				.is_some_and(|state| state.acked)
+	}
+
+	/// Sends the command made from the character's title to its reporter,
+	/// `None` for an unknown character and `Some(false)` when the reporter is offline
+	pub fn forward(
+		&mut self,
+		character: &str,
+		command: impl FnOnce(String) -> Command,
+	) -> Option<bool> {
+		let state = self.characters.get(character)?;
+		let command = command(state.title.clone());
+		let Some(reporter) = self.reporters.get_mut(&state.reporter) else {
+			return Some(false);
+		};
+		reporter.outboxes.retain(|outbox| !outbox.is_closed());
+		let mut delivered = false;
+		for outbox in &reporter.outboxes {
+			delivered |= outbox.send(command.clone()).is_ok();
+		}
+		Some(delivered)
+	}
+
+	/// Returns false when the character has no ongoing warn
+	pub fn ack(&mut self, character: &str, now: Instant) -> bool {
+		match self.characters.get_mut(character) {
+			Some(state) if state.warning(now) => {
+				state.acked = true;
+				true
+			}
+			_ => false,
+		}
+	}
+}
+
+/// State shared by every reporter connection
+pub struct AppState {
+	pub config: Config,
+	cooldown: Mutex<Cooldown>,
+	pub activity: Mutex<Activity>,
+}
+
+impl AppState {
+	pub fn new(config: Config) -> Self {
+		// known reporters are listed by `status` before they ever connect
+		let reporters = config
+			.reporters
+			.iter()
+			.map(|reporter| (reporter.name.clone(), ReporterState::default()))
+			.collect();
+		Self {
+			config,
+			cooldown: Mutex::new(Cooldown::default()),
+			activity: Mutex::new(Activity {
+				reporters,
+				..Activity::default()
+			}),
+		}
+	}
+
+	/// Records the event and tells whether it is to be sent
+	pub fn accept(&self, reporter: &str, title: &str, kind: &'static str) -> bool {
+		let now = Instant::now();
+		let character = character(title);
+		let mut activity = self.activity.lock().unwrap();
+		activity.record(reporter, title, kind, now);
+		let send = !activity.silenced(character, kind, now) && self.allow(character, kind);
+		if send {
+			activity.push_history(reporter, character, kind, now);
+		}
+		send
+	}
+
+	/// Returns true when recipients were alerted of the reporter's disconnect and are to be told it is back
+	pub fn connect(&self, reporter: &str, outbox: UnboundedSender<Command>) -> bool {
+		let mut activity = self.activity.lock().unwrap();
+		let state = activity.reporters.entry(reporter.to_string()).or_default();
+		state.connections += 1;
+		state.outboxes.push(outbox);
+		state.disconnected_at = None;
+		std::mem::take(&mut state.alerted)
+	}
+
+	/// Returns when the last connection of the reporter closed, `None` while others are open
+	pub fn disconnect(&self, reporter: &str) -> Option<Instant> {
+		let mut activity = self.activity.lock().unwrap();
+		let state = activity.reporters.entry(reporter.to_string()).or_default();
+		state.connections = state.connections.saturating_sub(1);
+		state.outboxes.retain(|outbox| !outbox.is_closed());
+		if state.connections > 0 {
+			return None;
+		}
+		let now = Instant::now();
+		state.disconnected_at = Some(now);
+		Some(now)
+	}
+
+	/// True once per disconnect that started at `since` and was not followed by a reconnect
+	pub fn disconnect_alert_due(&self, reporter: &str, since: Instant) -> bool {
+		let mut activity = self.activity.lock().unwrap();
+		let muted = activity.muted(Instant::now());
+		let Some(state) = activity.reporters.get_mut(reporter) else {
+			return false;
+		};
+		if state.connections > 0 || state.disconnected_at != Some(since) || state.alerted || muted {
+			return false;
+		}
+		state.alerted = true;
+		true
+	}
+
+	/// `kind` is `Warn`, `Snapshot` or `Reminder`, snapshots are spaced like warns
+	fn allow(&self, character: &str, kind: &'static str) -> bool {
+		let spacing = match kind {
+			"Warn" | "Snapshot" => self.config.warn_spacing,
+			_ => self.config.reminder_spacing,
+		};
+		self
+			.cooldown
+			.lock()
+			.unwrap()
+			.allow(character, kind, spacing)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::config::tests::example;
+	use crate::state::{Activity, AppState, Cooldown, WARN_RUN_GAP};
+	use std::time::{Duration, Instant};
+	use tokio::sync::mpsc;
+
+	#[test]
+	fn cooldown_per_character_and_kind() {
+		let mut cooldown = Cooldown::default();
+		let spacing = Duration::from_secs(60);
+		assert!(cooldown.allow("Alt A", "Warn", spacing));
+		assert!(!cooldown.allow("Alt A", "Warn", spacing));
+		assert!(cooldown.allow("Alt B", "Warn", spacing));
+		assert!(cooldown.allow("Alt A", "Reminder", spacing));
+		assert!(!cooldown.allow("Alt A", "Reminder", spacing));
+		assert!(cooldown.allow("Alt A", "Warn", Duration::ZERO));
+	}
+
+	#[test]
+	fn ack_and_mute() {
+		let mut activity = Activity::default();
+		let now = Instant::now();
+		assert!(!activity.ack("Alt A", now));
+		activity.record("Alice", "EVE - Alt A", "Warn", now);
+		assert!(!activity.silenced("Alt A", "Warn", now));
+		assert!(activity.ack("Alt A", now));
+		assert!(activity.silenced("Alt A", "Warn", now));
+		assert!(!activity.silenced("Alt A", "Reminder", now));
+		assert!(!activity.silenced("Alt B", "Warn", now));
+
+		// the ack ends with the sighting
+		let later = now + WARN_RUN_GAP * 2;
+		activity.record("Alice", "EVE - Alt A", "Warn", later);
+		assert!(!activity.silenced("Alt A", "Warn", later));
+
+		activity.muted_until = Some(later + Duration::from_secs(600));
+		assert!(activity.silenced("Alt B", "Reminder", later));
+	}
+
+	#[test]
+	fn history_of_sent_events() {
+		let state = AppState::new(example());
+		for _ in 0..100 {
+			state.accept("Alice", "EVE - Alt A", "Warn");
+		}
+		state.accept("Alice", "EVE - Alt A", "Reminder");
+		let activity = state.activity.lock().unwrap();
+		let kinds: Vec<_> = activity.history.iter().map(|entry| entry.kind).collect();
+		assert_eq!(kinds, ["Warn", "Reminder"]);
+	}
+
+	#[test]
+	fn reporter_disconnect() {
+		let state = AppState::new(example());
+		let outbox = || mpsc::unbounded_channel().0;
+		assert!(!state.connect("Alice", outbox()));
+		assert!(!state.connect("Alice", outbox()));
+		assert_eq!(state.disconnect("Alice"), None);
+		let since = state.disconnect("Alice").unwrap();
+		assert!(state.disconnect_alert_due("Alice", since));
+		assert!(!state.disconnect_alert_due("Alice", since));
+		assert!(state.connect("Alice", outbox()));
+
+		// a reconnect within the grace cancels the alert
+		let since = state.disconnect("Alice").unwrap();
+		state.connect("Alice", outbox());
+		assert!(!state.disconnect_alert_due("Alice", since));
+	}
+}
@@ -0,0 +1,230 @@
+use crate::config::CommandConfig;
+use crate::delivery::Cooldown;
+use crate::event::{Event, EventConsumer};
+use crate::tts::render_template;
+use crate::webhook::render_body;
+use anyhow::anyhow;
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+use tokio::sync::broadcast::Sender;
+use tracing::{debug, warn};
+
+fn command(config: &CommandConfig, event: &Event) -> anyhow::Result<Command> {
+	let kind: &'static str = event.into();
+	let title = event.title().unwrap_or_default();
+	let mut command = Command::new(&config.program);
+	for arg in &config.args {
+		command.arg(render_body(Some(arg), event)?);
+	}
+	command
+		.env("EVE_EVENT_TYPE", kind)
+		.env("EVE_TITLE", title)
+		.env("EVE_CHARACTER", render_template("{character}", title))
+		.stdin(Stdio::piped())
+		.stdout(Stdio::null())
+		.stderr(Stdio::piped())
+		.kill_on_drop(true);
+	Ok(command)
+}
+
+async fn run(config: &CommandConfig, event: &Event) -> anyhow::Result<()> {
+	let json = serde_json::to_vec(event)?;
+	let mut child = command(config, event)?
+		.spawn()
+		.map_err(|e| anyhow!("start {:?} failed: {e}", config.program))?;
+	let mut stdin = child.stdin.take().unwrap();
+	let output = tokio::time::timeout(config.timeout, async move {
+		// the program may exit without reading its stdin
+		let _ = stdin.write_all(&json).await;
+		drop(stdin);
+		child.wait_with_output().await
+	})
+	.await
+	.map_err(|_| anyhow!("{:?} timed out and was killed", config.program))??;
+	if !output.status.success() {
+		return Err(anyhow!(
+			"{:?} exited with {}: {}",
+			config.program,
+			output.status,
+			String::from_utf8_lossy(&output.stderr).trim()
+		));
+	}
+	debug!(?event, "command finished");
+	Ok(())
+}
+
+pub struct CommandController {
+	sender: Option<Sender<Event>>,
+	config: CommandConfig,
+}
+
+impl CommandController {
+	pub fn new(config: CommandConfig) -> Self {
+		Self {
+			sender: None,
+			config,
+		}
+	}
+}
+
+impl EventConsumer for CommandController {
+	fn inject(&mut self, sender: Sender<Event>) {
+		self.sender = Some(sender)
+	}
+
+	fn start(&self) -> anyhow::Result<()> {
+		if self.sender.is_none() {
+			return Err(anyhow!("There is no sender"));
+		}
+		if self.config.max_concurrent == 0 {
+			return Err(anyhow!("max_concurrent of command must be at least 1"));
+		}
+		let mut receiver = self.sender.clone().unwrap().subscribe();
+		let config = Arc::new(self.config.clone());
+		let slots = Arc::new(Semaphore::new(config.max_concurrent));
+		let mut cooldown = Cooldown::new(config.cooldown);
+
+		tokio::spawn(async move {
+			loop {
+				if let Ok(event) = receiver.recv().await {
+					// snooze and pause are control events, not alerts
+					if !matches!(event, Event::Warn { .. } | Event::Reminder { .. })
+						|| !cooldown.allow(&event)
+					{
+						continue;
+					}
+					let permit = slots.clone().acquire_owned().await.unwrap();
+					let config = config.clone();
+					tokio::spawn(async move {
+						if let Err(e) = run(&config, &event).await {
+							warn!("command failed: {e}");
+						}
+						drop(permit);
+					});
+				}
+			}
+		});
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::command::{CommandController, command, run};
+	use crate::config::CommandConfig;
+	use crate::event::{Event, EventConsumer};
+	use std::ffi::OsStr;
+	use std::time::{Duration, Instant};
+	use tokio::sync::broadcast;
+	use tokio::test;
+
+	fn shell(script: &str, timeout: Duration, max_concurrent: usize) -> CommandConfig {
+		CommandConfig {
+			program: "sh".into(),
+			args: vec!["-c".to_string(), script.to_string()],
+			timeout,
+			max_concurrent,
+			cooldown: Duration::ZERO,
+		}
+	}
+
+	fn warn(title: &str) -> Event {
+		Event::Warn {
+			title: title.to_string(),
+		}
+	}
+
+	#[test]
+	async fn args_and_env() {
+		let config = CommandConfig {
+			program: "notify".into(),
+			args: vec!["{type}".to_string(), "--who={character}".to_string()],
+			timeout: Duration::from_secs(1),
+			max_concurrent: 1,
+			cooldown: Duration::ZERO,
+		};
+		let event = Event::Warn {
+			title: "EVE - CHAR1".to_string(),
+		};
+		let command = command(&config, &event).unwrap();
+		let command = command.as_std();
+		let args: Vec<_> = command.get_args().collect();
+		assert_eq!(args, ["Warn", "--who=CHAR1"]);
+		let envs: Vec<_> = command.get_envs().collect();
+		assert!(envs.contains(&(OsStr::new("EVE_CHARACTER"), Some(OsStr::new("CHAR1")))));
+		assert!(envs.contains(&(OsStr::new("EVE_EVENT_TYPE"), Some(OsStr::new("Warn")))));
+	}
+
+	#[cfg(unix)]
+	#[test]
+	async fn timeout_kills() {
+		let started = Instant::now();
+		let config = shell("sleep 10", Duration::from_millis(200), 1);
+		let error = run(&config, &warn("EVE - CHAR1")).await.unwrap_err();
+		assert!(error.to_string().contains("timed out"));
+		assert!(started.elapsed() < Duration::from_secs(5));
+
+		let config = shell("echo broken >&2; exit 3", Duration::from_secs(5), 1);
+		let error = run(&config, &warn("EVE - CHAR1")).await.unwrap_err();
+		assert!(error.to_string().contains("broken"));
+	}
+
+	#[cfg(unix)]
+	#[test]
+	async fn stdin_is_event() {
+		let output = std::env::temp_dir().join("reporting-command-stdin.json");
+		let _ = std::fs::remove_file(&output);
+		let event = warn("EVE - CHAR1");
+		let mut config = shell(r#"cat > "$0""#, Duration::from_secs(5), 1);
+		config.args.push(output.to_string_lossy().into_owned());
+		run(&config, &event).await.unwrap();
+		let written = std::fs::read_to_string(&output).unwrap();
+		std::fs::remove_file(&output).unwrap();
+		assert_eq!(written, serde_json::to_string(&event).unwrap());
+	}
+
+	#[cfg(unix)]
+	#[test]
+	async fn max_concurrent() {
+		let dir = std::env::temp_dir().join("reporting-command-concurrent");
+		let _ = std::fs::remove_dir_all(&dir);
+		std::fs::create_dir_all(dir.join("running")).unwrap();
+		// every copy logs how many copies are running while it is
+		let script = r#"touch "$0/running/$$"; ls "$0/running" | wc -l >> "$0/log"; sleep 0.3; rm "$0/running/$$""#;
+		let mut config = shell(script, Duration::from_secs(5), 2);
+		config.args.push(dir.to_string_lossy().into_owned());
+		let (sender, _) = broadcast::channel(16);
+		let mut controller = CommandController::new(config);
+		controller.inject(sender.clone());
+		controller.start().unwrap();
+
+		sender.send(Event::Pause { paused: true }).unwrap();
+		for i in 0..5 {
+			sender.send(warn(&format!("EVE - CHAR{i}"))).unwrap();
+		}
+		let log = dir.join("log");
+		let started = Instant::now();
+		let counts: Vec<usize> = loop {
+			let counts: Vec<usize> = std::fs::read_to_string(&log)
+				.unwrap_or_default()
+				.lines()
+				.map(|line| line.trim().parse().unwrap())
+				.collect();
+			if counts.len() >= 5 || started.elapsed() > Duration::from_secs(10) {
+				break counts;
+			}
+			tokio::time::sleep(Duration::from_millis(50)).await;
+		};
+		// the pause event does not run the program
+		tokio::time::sleep(Duration::from_millis(500)).await;
+		assert_eq!(std::fs::read_to_string(&log).unwrap().lines().count(), 5);
+		std::fs::remove_dir_all(&dir).unwrap();
+		assert_eq!(counts.len(), 5);
+		assert!(counts.iter().all(|&count| count <= 2));
+		assert!(counts.contains(&2));
+	}
+}
@@ -0,0 +1,166 @@
+use crate::event::{Command, Event};
+use crate::get_char_titles;
+use anyhow::anyhow;
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::Sender;
+use tracing::info;
+
+const TEST_TITLE: &str = "EVE - TEST";
+/// Longer snoozes are refused, the minutes come from remote clients and would overflow `Instant`
+const MAX_SNOOZE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+static SNOOZED: RwLock<BTreeMap<String, Instant>> = RwLock::new(BTreeMap::new());
+static ACKNOWLEDGE_DURATION: RwLock<Duration> = RwLock::new(Duration::from_secs(600));
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+pub fn set_acknowledge_duration(duration: Duration) {
+	*ACKNOWLEDGE_DURATION.write().unwrap() = duration;
+}
+
+pub fn is_snoozed(title: &str) -> bool {
+	SNOOZED
+		.read()
+		.unwrap()
+		.get(title)
+		.is_some_and(|until| *until > Instant::now())
+}
+
+/// Remaining snooze of a character
+pub fn get_snooze(title: &str) -> Option<Duration> {
+	SNOOZED
+		.read()
+		.unwrap()
+		.get(title)
+		.and_then(|until| until.checked_duration_since(Instant::now()))
+}
+
+pub fn is_paused() -> bool {
+	PAUSED.load(Ordering::Relaxed)
+}
+
+fn snooze(title: &str, duration: Duration) -> anyhow::Result<()> {
+	let mut snoozed = SNOOZED.write().unwrap();
+	if duration.is_zero() {
+		snoozed.remove(title);
+	} else {
+		let until = Instant::now()
+			.checked_add(duration)
+			.ok_or_else(|| anyhow!("snooze of {duration:?} is too long"))?;
+		snoozed.insert(title.to_string(), until);
+	}
+	Ok(())
+}
+
+/// Applies a command and tells every consumer about it
+pub fn apply(command: Command, sender: &Sender<Event>) -> anyhow::Result<()> {
+	info!(?command, "apply command");
+	let (title, duration) = match command {
+		Command::Acknowledge { title } => (title, *ACKNOWLEDGE_DURATION.read().unwrap()),
+		Command::Snooze { title, minutes } => {
+			let duration = minutes
+				.checked_mul(60)
+				.map(Duration::from_secs)
+				.filter(|duration| *duration <= MAX_SNOOZE)
+				.ok_or_else(|| {
+					anyhow!(
+						"snooze of {minutes} minutes is longer than {} minutes",
+						MAX_SNOOZE.as_secs() / 60
+					)
+				})?;
+			(title, duration)
+		}
+		Command::Pause | Command::Resume => {
+			let paused = matches!(command, Command::Pause);
+			PAUSED.store(paused, Ordering::Relaxed);
+			let _ = sender.send(Event::Pause { paused });
+			return Ok(());
+		}
+		Command::Test { title } => {
+			let title = title.unwrap_or(TEST_TITLE.to_string());
+			let _ = sender.send(Event::Warn { title });
+			return Ok(());
+		}
+	};
+	let titles = title
+		.map(|title| vec![title])
+		.unwrap_or_else(get_char_titles);
+	for title in titles {
+		snooze(&title, duration)?;
+		let _ = sender.send(Event::Snooze {
+			title,
+			secs: duration.as_secs(),
+		});
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::control::{apply, get_snooze, is_paused, is_snoozed};
+	use crate::event::{Command, Event};
+	use tokio::sync::broadcast;
+	use tokio::test;
+
+	#[test]
+	async fn snooze() {
+		let (sender, mut receiver) = broadcast::channel(16);
+		apply(
+			Command::Snooze {
+				title: Some("EVE - SNOOZE".to_string()),
+				minutes: 5,
+			},
+			&sender,
+		)
+		.unwrap();
+		assert!(is_snoozed("EVE - SNOOZE"));
+		assert!(get_snooze("EVE - SNOOZE").unwrap().as_secs() > 290);
+		assert!(matches!(
+			receiver.recv().await.unwrap(),
+			Event::Snooze { secs: 300, .. }
+		));
+		apply(
+			Command::Snooze {
+				title: Some("EVE - SNOOZE".to_string()),
+				minutes: 0,
+			},
+			&sender,
+		)
+		.unwrap();
+		assert!(!is_snoozed("EVE - SNOOZE"));
+		assert!(matches!(
+			receiver.recv().await.unwrap(),
+			Event::Snooze { secs: 0, .. }
+		));
+
+		// remote clients pick the minutes, an overflow is refused instead of panicking
+		let overflow = Command::Snooze {
+			title: Some("EVE - SNOOZE".to_string()),
+			minutes: u64::MAX,
+		};
+		assert!(apply(overflow, &sender).is_err());
+		assert!(!is_snoozed("EVE - SNOOZE"));
+		assert!(receiver.try_recv().is_err());
+	}
+
+	#[test]
+	async fn pause_and_test() {
+		let (sender, mut receiver) = broadcast::channel(16);
+		apply(Command::Pause, &sender).unwrap();
+		assert!(is_paused());
+		apply(Command::Resume, &sender).unwrap();
+		assert!(!is_paused());
+		apply(Command::Test { title: None }, &sender).unwrap();
+		assert!(matches!(
+			receiver.recv().await.unwrap(),
+			Event::Pause { paused: true }
+		));
+		assert!(matches!(
+			receiver.recv().await.unwrap(),
+			Event::Pause { paused: false }
+		));
+		assert!(matches!(receiver.recv().await.unwrap(), Event::Warn { .. }));
+	}
+}
@@ -0,0 +1,210 @@
+use crate::event::Event;
+use anyhow::anyhow;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::collections::HashMap;
+use std::hash::{BuildHasher, RandomState};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// `try_spacing * 2^attempt` capped at `max_try_spacing`, then randomly shortened by up to half
+pub fn backoff(try_spacing: Duration, max_try_spacing: Duration, attempt: u32) -> Duration {
+	let delay = try_spacing
+		.saturating_mul(2u32.saturating_pow(attempt))
+		.min(max_try_spacing);
+	let jitter = RandomState::new().hash_one(attempt) % 1000;
+	delay - delay / 2 * jitter as u32 / 1000
+}
+
+pub fn header_map(headers: &HashMap<String, String>) -> anyhow::Result<HeaderMap> {
+	headers
+		.iter()
+		.map(|(name, value)| {
+			Ok((
+				HeaderName::from_str(name).map_err(|e| anyhow!("invalid header name {name}: {e}"))?,
+				HeaderValue::from_str(value).map_err(|e| anyhow!("invalid value of header {name}: {e}"))?,
+			))
+		})
+		.collect()
+}
+
+/// Warns and reminders fire on every captured frame, this lets one through per character and kind every `cooldown`
+pub struct Cooldown {
+	cooldown: Duration,
+	last_sent: HashMap<(String, &'static str), Instant>,
+}
+
+impl Cooldown {
+	pub fn new(cooldown: Duration) -> Self {
+		Self {
+			cooldown,
+			last_sent: HashMap::new(),
+		}
+	}
+
+	/// Events other than warns, snapshots and reminders always pass
+	pub fn allow(&mut self, event: &Event) -> bool {
+		let (Event::Warn { title } | Event::Snapshot { title, .. } | Event::Reminder { title }) = event
+		else {
+			return true;
+		};
+		let key = (title.clone(), event.into());
+		let now = Instant::now();
+		match self.last_sent.get(&key) {
+			Some(last_sent) if now.duration_since(*last_sent) < self.cooldown => false,
+			_ => {
+				self.last_sent.insert(key, now);
+				true
+			}
+		}
+	}
+}
+
+/// Seconds a server asks to wait, capped at `max`, negative, infinite and malformed values are ignored
+pub fn server_delay(value: &str, max: Duration) -> Option<Duration> {
+	let secs = value.trim().parse::<f64>().ok()?;
+	Duration::try_from_secs_f64(secs)
+		.ok()
+		.map(|delay| delay.min(max))
+}
+
+/// Sends the request made by `build`, connection failures, 429 and 5xx responses are retried `retries` times
+pub async fn send_with_retry(
+	build: impl Fn() -> RequestBuilder,
+	retries: u32,
+	retry_spacing: Duration,
+	max_retry_spacing: Duration,
+) -> anyhow::Result<Response> {
+	let mut attempt = 0;
+	loop {
+		let (error, retry_after) = match build().send().await {
+			Ok(response) if response.status().is_success() => return Ok(response),
+			Ok(response)
+				if response.status() == StatusCode::TOO_MANY_REQUESTS
+					|| response.status().is_server_error() =>
+			{
+				let retry_after = response
+					.headers()
+					.get(RETRY_AFTER)
+					.and_then(|value| value.to_str().ok())
+					.and_then(|value| server_delay(value, max_retry_spacing));
+				(
+					anyhow!("server responded {}", response.status()),
+					retry_after,
+				)
+			}
+			Ok(response) => {
+				let status = response.status();
+				let body = response.text().await.unwrap_or_default();
+				return Err(anyhow!("server responded {status}: {body}"));
+			}
+			// urls of webhooks and bots carry their tokens, keep them out of the logs
+			Err(e) => (e.without_url().into(), None),
+		};
+		if attempt >= retries {
+			return Err(error);
+		}
+		let delay = retry_after.unwrap_or_else(|| backoff(retry_spacing, max_retry_spacing, attempt));
+		warn!("request failed: {error}, retry in {delay:?}");
+		tokio::time::sleep(delay).await;
+		attempt += 1;
+		debug!(attempt, "retry request");
+	}
+}
+
+#[cfg(test)]
+pub mod tests {
+	use crate::delivery::{Cooldown, backoff, server_delay};
+	use crate::event::Event;
+	use crate::snapshot::Snapshot;
+	use axum::http::{HeaderMap, StatusCode, Uri};
+	use axum::response::{IntoResponse, Response};
+	use std::net::SocketAddr;
+	use std::sync::Arc;
+	use std::sync::atomic::{AtomicUsize, Ordering};
+	use std::time::Duration;
+	use tokio::sync::mpsc::{self, UnboundedReceiver};
+	use tokio::test;
+
+	/// A request received by the `record_server`
+	pub struct Recorded {
+		pub path: String,
+		pub headers: HeaderMap,
+		pub body: String,
+	}
+
+	/// Serves every path on a local port, the first `failures` requests are answered by `failure`,
+	/// the others are recorded and answered 200
+	pub async fn record_server(
+		failures: usize,
+		failure: fn() -> Response,
+	) -> (SocketAddr, UnboundedReceiver<Recorded>) {
+		let (record_sender, records) = mpsc::unbounded_channel();
+		let calls = Arc::new(AtomicUsize::new(0));
+		let app = axum::Router::new().fallback(move |uri: Uri, headers: HeaderMap, body: String| {
+			let calls = calls.clone();
+			let record_sender = record_sender.clone();
+			async move {
+				if calls.fetch_add(1, Ordering::Relaxed) < failures {
+					return failure();
+				}
+				let _ = record_sender.send(Recorded {
+					path: uri.path().to_string(),
+					headers,
+					body,
+				});
+				StatusCode::OK.into_response()
+			}
+		});
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let addr = listener.local_addr().unwrap();
+		tokio::spawn(async move { axum::serve(listener, app).await });
+		(addr, records)
+	}
+
+	#[test]
+	async fn backoff_and_cooldown() {
+		let spacing = Duration::from_secs(5);
+		let max = Duration::from_secs(60);
+		for attempt in 0..10 {
+			let expected = (spacing * 2u32.pow(attempt)).min(max);
+			let delay = backoff(spacing, max, attempt);
+			assert!(
+				delay <= expected && delay >= expected / 2,
+				"{attempt}: {delay:?}"
+			);
+		}
+		assert!(backoff(spacing, max, u32::MAX) <= max);
+
+		assert_eq!(server_delay("1.5", max), Some(Duration::from_millis(1500)));
+		assert_eq!(server_delay("100000", max), Some(max));
+		for malformed in ["-1", "inf", "NaN", "1e400", "soon"] {
+			assert_eq!(server_delay(malformed, max), None, "{malformed}");
+		}
+
+		let mut cooldown = Cooldown::new(Duration::from_secs(60));
+		let warn = |title: &str| Event::Warn {
+			title: title.to_string(),
+		};
+		let reminder = Event::Reminder {
+			title: "EVE - CHAR1".to_string(),
+		};
+		assert!(cooldown.allow(&warn("EVE - CHAR1")));
+		assert!(!cooldown.allow(&warn("EVE - CHAR1")));
+		assert!(cooldown.allow(&warn("EVE - CHAR2")));
+		let snapshot = Event::Snapshot {
+			title: "EVE - CHAR1".to_string(),
+			snapshot: Snapshot {
+				path: Default::default(),
+				name: "2026-01-01/00-00-00.000-CHAR1.png".to_string(),
+				thumbnail: None,
+			},
+		};
+		assert!(cooldown.allow(&snapshot));
+		assert!(!cooldown.allow(&snapshot));
+		assert!(cooldown.allow(&reminder));
+		assert!(cooldown.allow(&Event::Pause { paused: true }));
+		assert!(cooldown.allow(&Event::Pause { paused: true }));
+	}
+}
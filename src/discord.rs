@@ -0,0 +1,266 @@
+use crate::config::DiscordConfig;
+use crate::delivery::{Cooldown, send_with_retry, server_delay};
+use crate::event::{Event, EventConsumer};
+use crate::tts::render_template;
+use anyhow::anyhow;
+use reqwest::Client;
+use reqwest::header::HeaderMap;
+use reqwest::multipart::{Form, Part};
+use serde_json::{Value, json};
+use std::time::Duration;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use tokio::sync::broadcast::Sender;
+use tracing::{debug, warn};
+
+const WARN_COLOR: u32 = 0xE74C3C;
+const REMINDER_COLOR: u32 = 0xF1C40F;
+const SNAPSHOT_NAME: &str = "snapshot.png";
+
+/// Webhook message of a warn, its snapshot or a reminder, other events are not sent
+fn message(config: &DiscordConfig, event: &Event, with_image: bool) -> Option<Value> {
+	let (kind, title, color, mention) = match event {
+		Event::Warn { title } | Event::Snapshot { title, .. } => {
+			("Warn", title, WARN_COLOR, &config.warn_mention)
+		}
+		Event::Reminder { title } => ("Reminder", title, REMINDER_COLOR, &config.reminder_mention),
+		Event::Snooze { .. } | Event::Pause { .. } => return None,
+	};
+	let mut embed = json!({
+		"title": format!("{kind}: {}", render_template("{character}", title)),
+		"color": color,
+		"timestamp": OffsetDateTime::now_utc().format(&Rfc3339).ok(),
+		"footer": { "text": title },
+	});
+	// the image follows a warn that mentioned already
+	let mention = if with_image {
+		embed["image"] = json!({ "url": format!("attachment://{SNAPSHOT_NAME}") });
+		None
+	} else {
+		mention.clone()
+	};
+	Some(json!({
+		"content": mention.unwrap_or_default(),
+		"username": config.username,
+		"embeds": [embed],
+		"allowed_mentions": { "parse": ["everyone", "roles", "users"] },
+	}))
+}
+
+/// How long to wait out the bucket when Discord reports no requests left in it, capped at `max`
+fn rate_limit_delay(headers: &HeaderMap, max: Duration) -> Option<Duration> {
+	let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+	if header("x-ratelimit-remaining")?.trim() != "0" {
+		return None;
+	}
+	server_delay(header("x-ratelimit-reset-after")?, max)
+}
+
+struct Discord {
+	client: Client,
+	config: DiscordConfig,
+}
+
+impl Discord {
+	fn new(config: DiscordConfig) -> anyhow::Result<Self> {
+		let client = Client::builder().timeout(config.timeout).build()?;
+		Ok(Self { client, config })
+	}
+
+	async fn send(&self, event: &Event) -> anyhow::Result<()> {
+		let snapshot = match event {
+			// the warn itself was sent already, a snapshot without its image would repeat it
+			Event::Snapshot { snapshot, .. } => {
+				if !self.config.attach_snapshot {
+					return Ok(());
+				}
+				match tokio::fs::read(&snapshot.path).await {
+					Ok(png) => Some(png),
+					Err(e) => {
+						warn!("read snapshot {:?} failed: {e}", snapshot.path);
+						return Ok(());
+					}
+				}
+			}
+			_ => None,
+		};
+		let Some(message) = message(&self.config, event, snapshot.is_some()) else {
+			return Ok(());
+		};
+		let response = send_with_retry(
+			|| {
+				let request = self.client.post(self.config.url.clone());
+				match &snapshot {
+					Some(png) => {
+						let form = Form::new().text("payload_json", message.to_string()).part(
+							"files[0]",
+							Part::bytes(png.clone()).file_name(SNAPSHOT_NAME),
+						);
+						request.multipart(form)
+					}
+					None => request.json(&message),
+				}
+			},
+			self.config.retries,
+			self.config.retry_spacing,
+			self.config.max_retry_spacing,
+		)
+		.await?;
+		if let Some(delay) = rate_limit_delay(response.headers(), self.config.max_retry_spacing) {
+			debug!(?delay, "discord rate limit bucket exhausted");
+			tokio::time::sleep(delay).await;
+		}
+		Ok(())
+	}
+}
+
+pub struct DiscordController {
+	sender: Option<Sender<Event>>,
+	config: DiscordConfig,
+}
+
+impl DiscordController {
+	pub fn new(config: DiscordConfig) -> Self {
+		Self {
+			sender: None,
+			config,
+		}
+	}
+}
+
+impl EventConsumer for DiscordController {
+	fn inject(&mut self, sender: Sender<Event>) {
+		self.sender = Some(sender)
+	}
+
+	fn start(&self) -> anyhow::Result<()> {
+		if self.sender.is_none() {
+			return Err(anyhow!("There is no sender"));
+		}
+		let mut receiver = self.sender.clone().unwrap().subscribe();
+		let discord = Discord::new(self.config.clone())?;
+		let mut cooldown = Cooldown::new(self.config.cooldown);
+
+		tokio::spawn(async move {
+			loop {
+				if let Ok(event) = receiver.recv().await {
+					if !cooldown.allow(&event) {
+						continue;
+					}
+					if let Err(e) = discord.send(&event).await {
+						warn!("discord delivery failed: {e}");
+					}
+				}
+			}
+		});
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::config::DiscordConfig;
+	use crate::delivery::tests::record_server;
+	use crate::discord::{Discord, rate_limit_delay};
+	use crate::event::Event;
+	use crate::snapshot::Snapshot;
+	use axum::http::{HeaderMap, StatusCode, header};
+	use axum::response::IntoResponse;
+	use std::time::Duration;
+	use tokio::test;
+
+	#[test]
+	async fn embeds_and_rate_limit() {
+		// a stand-in for discord rate limiting the first request
+		let (addr, mut records) = record_server(1, || {
+			(
+				StatusCode::TOO_MANY_REQUESTS,
+				[(header::RETRY_AFTER, "0.01")],
+			)
+				.into_response()
+		})
+		.await;
+
+		let discord = Discord::new(DiscordConfig {
+			url: format!("http://{addr}/webhook").parse().unwrap(),
+			username: None,
+			warn_mention: Some("@here".to_string()),
+			reminder_mention: None,
+			attach_snapshot: true,
+			timeout: Duration::from_secs(5),
+			retries: 2,
+			retry_spacing: Duration::from_millis(10),
+			max_retry_spacing: Duration::from_secs(1),
+			cooldown: Duration::ZERO,
+		})
+		.unwrap();
+
+		let warn = Event::Warn {
+			title: "EVE - CHAR1".to_string(),
+		};
+		discord.send(&warn).await.unwrap();
+		let recorded = records.recv().await.unwrap();
+		assert_eq!(recorded.headers[header::CONTENT_TYPE], "application/json");
+		assert!(recorded.body.contains("@here"));
+		assert!(recorded.body.contains("Warn: CHAR1"));
+
+		// the snapshot follows without mentioning again
+		let snapshot_path = std::env::temp_dir().join(format!("discord-{}.png", std::process::id()));
+		tokio::fs::write(&snapshot_path, b"png").await.unwrap();
+		let snapshot = Snapshot {
+			path: snapshot_path.clone(),
+			name: "2026-01-01/00-00-00.000-CHAR1.png".to_string(),
+			thumbnail: None,
+		};
+		discord
+			.send(&Event::Snapshot {
+				title: "EVE - CHAR1".to_string(),
+				snapshot,
+			})
+			.await
+			.unwrap();
+		let recorded = records.recv().await.unwrap();
+		let (content_type, body) = (&recorded.headers[header::CONTENT_TYPE], recorded.body);
+		assert!(
+			content_type
+				.to_str()
+				.unwrap()
+				.starts_with("multipart/form-data")
+		);
+		assert!(!body.contains("@here"));
+		assert!(body.contains("attachment://snapshot.png"));
+		assert!(body.contains("Warn: CHAR1"));
+
+		let reminder = Event::Reminder {
+			title: "EVE - CHAR1".to_string(),
+		};
+		discord.send(&reminder).await.unwrap();
+		let recorded = records.recv().await.unwrap();
+		let (content_type, body) = (&recorded.headers[header::CONTENT_TYPE], recorded.body);
+		assert_eq!(content_type, "application/json");
+		assert!(!body.contains("@here"));
+		assert!(body.contains("Reminder: CHAR1"));
+
+		discord.send(&Event::Pause { paused: true }).await.unwrap();
+		assert!(records.try_recv().is_err());
+		tokio::fs::remove_file(snapshot_path).await.unwrap();
+
+		let max = Duration::from_secs(60);
+		let bucket = |remaining: &'static str, reset_after: &'static str| {
+			let mut headers = HeaderMap::new();
+			headers.insert("x-ratelimit-remaining", remaining.parse().unwrap());
+			headers.insert("x-ratelimit-reset-after", reset_after.parse().unwrap());
+			headers
+		};
+		assert_eq!(
+			rate_limit_delay(&bucket("0", "0.5"), max),
+			Some(Duration::from_millis(500))
+		);
+		assert_eq!(rate_limit_delay(&bucket("1", "0.5"), max), None);
+		assert_eq!(rate_limit_delay(&bucket("0", "1e9"), max), Some(max));
+		for malformed in ["-1", "inf", "NaN"] {
+			assert_eq!(rate_limit_delay(&bucket("0", malformed), max), None);
+		}
+	}
+}
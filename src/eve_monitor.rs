@@ -1,14 +1,43 @@
 use crate::config::*;
+use crate::control::{is_paused, is_snoozed};
 use crate::eve::EveClient;
 use crate::event::{Event, EventProducer};
+use crate::snapshot;
 use anyhow::anyhow;
 use async_trait::async_trait;
+use image::RgbaImage;
+use std::sync::Arc;
 use tokio::sync::broadcast::Sender;
+use tracing::warn;
 
 pub struct EveMonitor {
 	pub eve_client: EveClient,
 	pub character_config: Character,
 	pub sender: Option<Sender<Event>>,
+	pub snapshot_config: Option<SnapshotConfig>,
+}
+
+/// Saves the snapshot and sends it in an event of its own, the warns of the frames never wait for it
+async fn send_snapshot(
+	sender: Sender<Event>,
+	config: SnapshotConfig,
+	title: String,
+	region: Region,
+	capture: Arc<RgbaImage>,
+) {
+	let snapshot = {
+		let title = title.clone();
+		tokio::task::spawn_blocking(move || snapshot::capture(&config, &title, &region, &capture))
+			.await
+			.map_err(anyhow::Error::from)
+			.and_then(|result| result)
+	};
+	match snapshot {
+		Ok(snapshot) => {
+			let _ = sender.send(Event::Snapshot { title, snapshot });
+		}
+		Err(e) => warn!("save snapshot failed: {e}"),
+	}
 }
 
 #[async_trait]
@@ -26,18 +55,39 @@ impl EventProducer for EveMonitor {
 		let char_cfg = self.character_config.clone();
 		let sender = self.sender.clone().unwrap();
 		let title = self.eve_client.title.clone();
+		let snapshot_config = self.snapshot_config.clone();
 		tokio::spawn(async move {
 			let mut state = (false, false);
+			// taken once when a warn starts, until it clears
+			let mut snapshot_taken = false;
 			loop {
 				if let Ok(capture) = capture_receiver.recv().await {
-					if char_cfg.warn_region.check_in_image(&capture) {
+					let snoozed = is_snoozed(&title) || is_paused();
+					let warning = char_cfg.warn_region.check_in_image(&capture);
+					if !warning {
+						snapshot_taken = false;
+					}
+					if warning && !snoozed {
 						let _ = sender.send(Event::Warn {
 							title: title.clone(),
 						});
+						if let Some(config) = &snapshot_config
+							&& !snapshot_taken
+						{
+							snapshot_taken = true;
+							tokio::spawn(send_snapshot(
+								sender.clone(),
+								config.clone(),
+								title.clone(),
+								char_cfg.warn_region.clone(),
+								capture.clone(),
+							));
+						}
 					}
 					if char_cfg
 						.reminder_regions
 						.check_reminder(&capture, &mut state)
+						&& !snoozed
 					{
 						let _ = sender.send(Event::Reminder {
 							title: title.clone(),
@@ -51,12 +101,16 @@ impl EventProducer for EveMonitor {
 }
 
 impl EveMonitor {
-	pub fn new(character_config: Character) -> anyhow::Result<Self> {
+	pub fn new(
+		character_config: Character,
+		snapshot_config: Option<SnapshotConfig>,
+	) -> anyhow::Result<Self> {
 		let eve_client = EveClient::new_from_title(&character_config.title)?;
 		Ok(Self {
 			eve_client,
 			character_config,
 			sender: None,
+			snapshot_config,
 		})
 	}
 }
@@ -1,12 +1,63 @@
+use crate::snapshot::Snapshot;
+use crate::status;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use strum::{EnumIs, IntoStaticStr};
 use tokio::sync::broadcast::{self, Sender};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, EnumIs, IntoStaticStr)]
 #[serde(tag = "type")]
 pub enum Event {
-	Warn { title: String },
-	Reminder { title: String },
+	Warn {
+		title: String,
+	},
+	/// Follows the first warn of a sighting once its snapshot is saved, only consumers showing the image send it
+	Snapshot {
+		title: String,
+		snapshot: Snapshot,
+	},
+	Reminder {
+		title: String,
+	},
+	/// Alerts of `title` are silenced for `secs`, zero means resumed
+	Snooze {
+		title: String,
+		secs: u64,
+	},
+	Pause {
+		paused: bool,
+	},
+}
+
+impl Event {
+	pub fn title(&self) -> Option<&str> {
+		match self {
+			Self::Warn { title }
+			| Self::Snapshot { title, .. }
+			| Self::Reminder { title }
+			| Self::Snooze { title, .. } => Some(title),
+			Self::Pause { .. } => None,
+		}
+	}
+}
+
+/// Control commands accepted from clients, a missing title targets every character
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Command {
+	Acknowledge {
+		title: Option<String>,
+	},
+	Snooze {
+		title: Option<String>,
+		minutes: u64,
+	},
+	Pause,
+	Resume,
+	/// Sends a warn through every consumer
+	Test {
+		title: Option<String>,
+	},
 }
 
 #[async_trait]
@@ -30,6 +81,7 @@ pub struct EventCenter {
 impl EventCenter {
 	pub fn init() -> Self {
 		let (sender, _) = broadcast::channel(16);
+		status::spawn_recorder(&sender);
 		Self {
 			sender,
 			producers: Vec::new(),
@@ -0,0 +1,398 @@
+use crate::config::ReportMethod;
+use crate::event::{Event, EventConsumer};
+use crate::status::now_millis;
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use time::format_description::BorrowedFormatItem;
+use time::macros::format_description;
+use time::{OffsetDateTime, PrimitiveDateTime, UtcOffset};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::broadcast::Sender;
+use tracing::{info, warn};
+
+const DATE_TIME_FORMAT: &[BorrowedFormatItem<'static>] =
+	format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
+/// Warns closer than this belong to the same sighting in summaries
+const WARN_RUN_GAP: u64 = 30_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggedEvent {
+	pub at: u64,
+	#[serde(flatten)]
+	pub event: Event,
+}
+
+/// `events.jsonl` is rotated to `events.jsonl.1`, `events.jsonl.2`... once it reaches `max_size`
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+	if index == 0 {
+		return path.to_path_buf();
+	}
+	let mut name = path.as_os_str().to_owned();
+	name.push(format!(".{index}"));
+	PathBuf::from(name)
+}
+
+pub struct EventLog {
+	path: PathBuf,
+	max_size: u64,
+	max_files: usize,
+	file: Option<File>,
+	size: u64,
+}
+
+impl EventLog {
+	pub fn new(path: &Path, max_size: u64, max_files: usize) -> Self {
+		Self {
+			path: path.to_path_buf(),
+			max_size,
+			max_files,
+			file: None,
+			size: 0,
+		}
+	}
+
+	async fn rotate(&mut self) -> anyhow::Result<()> {
+		self.file = None;
+		let _ = tokio::fs::remove_file(rotated_path(&self.path, self.max_files)).await;
+		for index in (0..self.max_files).rev() {
+			let from = rotated_path(&self.path, index);
+			if tokio::fs::try_exists(&from).await? {
+				tokio::fs::rename(&from, rotated_path(&self.path, index + 1)).await?;
+			}
+		}
+		Ok(())
+	}
+
+	pub async fn append(&mut self, logged: &LoggedEvent) -> anyhow::Result<()> {
+		let mut line = serde_json::to_string(logged)?;
+		line.push('\n');
+		if self.file.is_some() && self.size + line.len() as u64 > self.max_size {
+			self.rotate().await?;
+		}
+		if self.file.is_none() {
+			let file = OpenOptions::new()
+				.create(true)
+				.append(true)
+				.open(&self.path)
+				.await?;
+			self.size = file.metadata().await?.len();
+			self.file = Some(file);
+		}
+		let file = self.file.as_mut().unwrap();
+		file.write_all(line.as_bytes()).await?;
+		file.flush().await?;
+		self.size += line.len() as u64;
+		Ok(())
+	}
+
+	/// Every logged event, oldest file first
+	pub async fn read_all(path: &Path, max_files: usize) -> anyhow::Result<Vec<LoggedEvent>> {
+		let mut events = Vec::new();
+		for index in (0..=max_files).rev() {
+			let Ok(file) = File::open(rotated_path(path, index)).await else {
+				continue;
+			};
+			let mut lines = BufReader::new(file).lines();
+			while let Some(line) = lines.next_line().await? {
+				match serde_json::from_str::<LoggedEvent>(&line) {
+					Ok(logged) => events.push(logged),
+					Err(e) => warn!("skip broken event log line {line}: {e}"),
+				}
+			}
+		}
+		Ok(events)
+	}
+}
+
+pub struct EventLogController {
+	sender: Option<Sender<Event>>,
+	path: PathBuf,
+	max_size: u64,
+	max_files: usize,
+}
+
+impl EventLogController {
+	pub fn new(path: &Path, max_size: u64, max_files: usize) -> Self {
+		Self {
+			sender: None,
+			path: path.to_path_buf(),
+			max_size,
+			max_files,
+		}
+	}
+}
+
+impl EventConsumer for EventLogController {
+	fn inject(&mut self, sender: Sender<Event>) {
+		self.sender = Some(sender)
+	}
+
+	fn start(&self) -> anyhow::Result<()> {
+		if self.sender.is_none() {
+			return Err(anyhow!("There is no sender"));
+		}
+		let mut receiver = self.sender.clone().unwrap().subscribe();
+		let mut event_log = EventLog::new(&self.path, self.max_size, self.max_files);
+		info!("logging events to {:?}", self.path);
+
+		tokio::spawn(async move {
+			loop {
+				if let Ok(event) = receiver.recv().await {
+					// the warn before it was logged already
+					if event.is_snapshot() {
+						continue;
+					}
+					let logged = LoggedEvent {
+						at: now_millis(),
+						event,
+					};
+					if let Err(e) = event_log.append(&logged).await {
+						warn!("write event log failed: {e}");
+					}
+				}
+			}
+		});
+
+		Ok(())
+	}
+}
+
+/// Filters of the `--query` command
+#[derive(Debug, Default)]
+pub struct Query {
+	pub character: Option<String>,
+	pub kind: Option<String>,
+	pub since: Option<u64>,
+	pub until: Option<u64>,
+	pub summary: bool,
+}
+
+fn arg_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+	args
+		.iter()
+		.position(|arg| arg == name)
+		.and_then(|index| args.get(index + 1))
+		.map(String::as_str)
+}
+
+fn parse_time(value: &str, offset: UtcOffset) -> anyhow::Result<u64> {
+	let value = if value.len() == 16 {
+		format!("{value}:00")
+	} else {
+		value.to_string()
+	};
+	let date_time = PrimitiveDateTime::parse(&value, DATE_TIME_FORMAT)
+		.map_err(|e| anyhow!("invalid time {value}, expect \"YYYY-MM-DD HH:MM[:SS]\": {e}"))?;
+	Ok((date_time.assume_offset(offset).unix_timestamp_nanos() / 1_000_000) as u64)
+}
+
+fn parse_last(value: &str) -> anyhow::Result<Duration> {
+	let invalid = || anyhow!("invalid duration {value}, expect e.g. 30m, 2h or 1d");
+	let unit_start = value.char_indices().last().map_or(0, |(i, _)| i);
+	let (number, unit) = value.split_at(unit_start);
+	let number = number.parse::<u64>().map_err(|_| invalid())?;
+	let unit_secs = match unit {
+		"m" => 60,
+		"h" => 3600,
+		"d" => 86400,
+		_ => return Err(invalid()),
+	};
+	number
+		.checked_mul(unit_secs)
+		.map(Duration::from_secs)
+		.ok_or_else(|| anyhow!("duration {value} is too long"))
+}
+
+impl Query {
+	pub fn from_args(args: &[String], offset: UtcOffset) -> anyhow::Result<Self> {
+		let mut since = arg_value(args, "--since")
+			.map(|value| parse_time(value, offset))
+			.transpose()?;
+		if let Some(last) = arg_value(args, "--last") {
+			let last = u64::try_from(parse_last(last)?.as_millis()).unwrap_or(u64::MAX);
+			since = Some(now_millis().saturating_sub(last));
+		}
+		Ok(Self {
+			character: arg_value(args, "--character").map(str::to_string),
+			kind: arg_value(args, "--type").map(str::to_string),
+			since,
+			until: arg_value(args, "--until")
+				.map(|value| parse_time(value, offset))
+				.transpose()?,
+			summary: args.iter().any(|arg| arg == "--summary"),
+		})
+	}
+
+	pub fn matches(&self, logged: &LoggedEvent) -> bool {
+		let kind: &'static str = (&logged.event).into();
+		self.character.as_ref().is_none_or(|character| {
+			logged
+				.event
+				.title()
+				.is_some_and(|title| title.contains(character.as_str()))
+		}) && self
+			.kind
+			.as_ref()
+			.is_none_or(|expected| expected.eq_ignore_ascii_case(kind))
+			&& self.since.is_none_or(|since| logged.at >= since)
+			&& self.until.is_none_or(|until| logged.at <= until)
+	}
+}
+
+fn format_time(at: u64, offset: UtcOffset) -> String {
+	OffsetDateTime::from_unix_timestamp_nanos(at as i128 * 1_000_000)
+		.map(|date_time| date_time.to_offset(offset))
+		.ok()
+		.and_then(|date_time| date_time.format(DATE_TIME_FORMAT).ok())
+		.unwrap_or_else(|| at.to_string())
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Summary {
+	sightings: usize,
+	warns: usize,
+	reminders: usize,
+	first: u64,
+	last: u64,
+}
+
+fn summarize(events: &[LoggedEvent]) -> BTreeMap<String, Summary> {
+	let mut summaries = BTreeMap::<String, Summary>::new();
+	for logged in events {
+		let (Event::Warn { title } | Event::Reminder { title }) = &logged.event else {
+			continue;
+		};
+		let summary = summaries.entry(title.clone()).or_insert_with(|| Summary {
+			first: logged.at,
+			..Default::default()
+		});
+		if logged.event.is_warn() {
+			if summary.warns == 0 || logged.at.saturating_sub(summary.last) > WARN_RUN_GAP {
+				summary.sightings += 1;
+			}
+			summary.warns += 1;
+		} else {
+			summary.reminders += 1;
+		}
+		summary.last = logged.at;
+	}
+	summaries
+}
+
+/// Prints the logged events matching the command line filters
+pub async fn query(args: &[String], offset: UtcOffset) -> anyhow::Result<()> {
+	let (path, max_files) = crate::config::Config::init()
+		.await?
+		.report_methods
+		.into_iter()
+		.find_map(|method| match method {
+			ReportMethod::EventLog {
+				path, max_files, ..
+			} => Some((path, max_files)),
+			_ => None,
+		})
+		.ok_or_else(|| anyhow!("There is no EventLog report method in settings.toml"))?;
+	let query = Query::from_args(args, offset)?;
+	let events = EventLog::read_all(&path, max_files)
+		.await?
+		.into_iter()
+		.filter(|logged| query.matches(logged))
+		.collect::<Vec<_>>();
+
+	if query.summary {
+		for (title, summary) in summarize(&events) {
+			println!(
+				"{title}: {} sightings ({} warns), {} reminders, {} - {}",
+				summary.sightings,
+				summary.warns,
+				summary.reminders,
+				format_time(summary.first, offset),
+				format_time(summary.last, offset),
+			);
+		}
+	} else {
+		for logged in &events {
+			let kind: &'static str = (&logged.event).into();
+			let detail = match &logged.event {
+				Event::Snooze { title, secs } => format!("{title} {secs}s"),
+				Event::Pause { paused } => paused.to_string(),
+				event => event.title().unwrap_or_default().to_string(),
+			};
+			println!("{}  {kind:<8} {detail}", format_time(logged.at, offset));
+		}
+	}
+	println!("{} events", events.len());
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::event::Event;
+	use crate::event_log::{EventLog, LoggedEvent, Query, parse_last, parse_time, summarize};
+	use std::time::Duration;
+	use time::UtcOffset;
+	use tokio::test;
+
+	fn warn(at: u64, title: &str) -> LoggedEvent {
+		LoggedEvent {
+			at,
+			event: Event::Warn {
+				title: title.to_string(),
+			},
+		}
+	}
+
+	#[test]
+	async fn rotate() {
+		let dir = std::env::temp_dir().join(format!("event-log-{}", std::process::id()));
+		tokio::fs::create_dir_all(&dir).await.unwrap();
+		let path = dir.join("events.jsonl");
+		let mut event_log = EventLog::new(&path, 100, 2);
+		for at in 0..10 {
+			event_log.append(&warn(at, "EVE - CHAR1")).await.unwrap();
+		}
+		let events = EventLog::read_all(&path, 2).await.unwrap();
+		assert!(events.len() < 10);
+		assert_eq!(events.last().unwrap().at, 9);
+		assert!(events.windows(2).all(|pair| pair[0].at < pair[1].at));
+		tokio::fs::remove_dir_all(&dir).await.unwrap();
+	}
+
+	#[test]
+	async fn filter_and_summary() {
+		let offset = UtcOffset::UTC;
+		let args = [
+			"--character",
+			"CHAR1",
+			"--type",
+			"warn",
+			"--since",
+			"1970-01-01 00:00",
+		]
+		.map(str::to_string);
+		let query = Query::from_args(&args, offset).unwrap();
+		assert!(query.matches(&warn(1, "EVE - CHAR1")));
+		assert!(!query.matches(&warn(1, "EVE - CHAR2")));
+		assert_eq!(parse_time("1970-01-01 00:01", offset).unwrap(), 60_000);
+		assert!(parse_time("yesterday", offset).is_err());
+		assert_eq!(parse_last("30m").unwrap(), Duration::from_secs(1800));
+		assert!(parse_last("").is_err());
+		assert!(parse_last("3天").is_err());
+		assert!(parse_last("18446744073709551615d").is_err());
+		let args = ["--last", "213503982334601d"].map(str::to_string);
+		assert_eq!(Query::from_args(&args, offset).unwrap().since, Some(0));
+
+		let events = [
+			warn(0, "EVE - CHAR1"),
+			warn(500, "EVE - CHAR1"),
+			warn(100_000, "EVE - CHAR1"),
+		];
+		let summary = &summarize(&events)["EVE - CHAR1"];
+		assert_eq!((summary.sightings, summary.warns), (2, 3));
+		assert_eq!((summary.first, summary.last), (0, 100_000));
+	}
+}
@@ -0,0 +1,86 @@
+use anyhow::anyhow;
+use std::str::FromStr;
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+	HOT_KEY_MODIFIERS, MOD_ALT, MOD_CONTROL, MOD_NOREPEAT, MOD_SHIFT, MOD_WIN, RegisterHotKey,
+};
+use windows::Win32::UI::WindowsAndMessaging::{GetMessageW, MSG, WM_HOTKEY};
+
+/// A global shortcut such as `Ctrl+Alt+S` or `Shift+F9`
+#[derive(Debug, Clone, Copy)]
+pub struct Hotkey {
+	modifiers: HOT_KEY_MODIFIERS,
+	key: u32,
+}
+
+impl FromStr for Hotkey {
+	type Err = anyhow::Error;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let mut modifiers = HOT_KEY_MODIFIERS(0);
+		let mut key = None;
+		for part in s.split('+').map(|part| part.trim().to_ascii_uppercase()) {
+			match part.as_str() {
+				"CTRL" | "CONTROL" => modifiers |= MOD_CONTROL,
+				"ALT" => modifiers |= MOD_ALT,
+				"SHIFT" => modifiers |= MOD_SHIFT,
+				"WIN" => modifiers |= MOD_WIN,
+				_ if key.is_some() => return Err(anyhow!("hotkey {s} has more than one key")),
+				_ => key = Some(virtual_key(&part).ok_or_else(|| anyhow!("unknown key {part}"))?),
+			}
+		}
+		let key = key.ok_or_else(|| anyhow!("hotkey {s} has no key"))?;
+		Ok(Self { modifiers, key })
+	}
+}
+
+fn virtual_key(key: &str) -> Option<u32> {
+	let mut chars = key.chars();
+	match (chars.next(), chars.next()) {
+		(Some(c @ ('A'..='Z' | '0'..='9')), None) => Some(c as u32),
+		(Some('F'), Some(_)) => match key[1..].parse::<u32>() {
+			Ok(n @ 1..=24) => Some(0x70 + n - 1),
+			_ => None,
+		},
+		_ => None,
+	}
+}
+
+impl Hotkey {
+	/// Calls `on_press` from a dedicated thread every time the hotkey is pressed
+	pub fn listen(self, on_press: impl Fn() + Send + 'static) -> anyhow::Result<()> {
+		let (result_sender, result_receiver) = std::sync::mpsc::channel();
+		// a hotkey is bound to the thread registering it, so the message loop runs there too
+		std::thread::spawn(move || {
+			let registered = unsafe { RegisterHotKey(None, 1, self.modifiers | MOD_NOREPEAT, self.key) };
+			let is_ok = registered.is_ok();
+			let _ = result_sender.send(registered);
+			if !is_ok {
+				return;
+			}
+			let mut msg = MSG::default();
+			while unsafe { GetMessageW(&mut msg, None, 0, 0) }.as_bool() {
+				if msg.message == WM_HOTKEY {
+					on_press();
+				}
+			}
+		});
+		result_receiver.recv()??;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::hotkey::Hotkey;
+	use std::str::FromStr;
+
+	#[test]
+	fn parse() {
+		let hotkey = Hotkey::from_str("Ctrl+Alt+S").unwrap();
+		assert_eq!(hotkey.modifiers.0, 3);
+		assert_eq!(hotkey.key, 'S' as u32);
+		assert_eq!(Hotkey::from_str("shift + f9").unwrap().key, 0x78);
+		assert!(Hotkey::from_str("Ctrl+Alt").is_err());
+		assert!(Hotkey::from_str("Ctrl+S+D").is_err());
+		assert!(Hotkey::from_str("Ctrl+F30").is_err());
+	}
+}
@@ -1,8 +1,9 @@
 use crate::config::Config;
 use crate::eve::EveClient;
 use crate::eve_monitor::EveMonitor;
-use crate::event::EventCenter;
-use std::sync::RwLock;
+use crate::event::{Command, EventCenter};
+use crate::hotkey::Hotkey;
+use std::sync::{OnceLock, RwLock};
 use std::time::Duration;
 use time::UtcOffset;
 use tokio::fs::File;
@@ -13,37 +14,71 @@ use tracing_subscriber::fmt::time::{LocalTime, OffsetTime};
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 
+mod command;
 mod config;
+mod control;
+mod delivery;
+mod discord;
 mod eve;
 mod eve_monitor;
 mod event;
+mod event_log;
+mod hotkey;
 mod image_checker;
+mod mqtt;
 mod notification;
 mod reverse_websocket;
+mod snapshot;
 mod sse;
+mod status;
+mod telegram;
+mod tts;
 mod voice_player;
+mod webhook;
 
 static CHAR_TITLES: RwLock<Vec<String>> = RwLock::new(Vec::new());
+static CONFIG: OnceLock<Config> = OnceLock::new();
 
 pub fn get_char_titles() -> Vec<String> {
 	let char_titles_lock = CHAR_TITLES.read().unwrap();
 	char_titles_lock.clone()
 }
 
+pub fn get_config() -> Option<&'static Config> {
+	CONFIG.get()
+}
+
 #[instrument]
 async fn entry_point() -> anyhow::Result<()> {
 	let config = Config::init()
 		.await
 		.inspect_err(|e| error!("reading config failed: {e}"))?;
+	let _ = CONFIG.set(config.clone());
 	config.characters.iter().for_each(|char| {
 		let mut char_titles = CHAR_TITLES.write().unwrap();
 		char_titles.push(char.title.clone());
 	});
 	let mut event_center = EventCenter::init();
+	control::set_acknowledge_duration(config.snooze.acknowledge_duration);
+	if let Some(hotkey) = &config.snooze.hotkey {
+		let sender = event_center.sender.clone();
+		hotkey
+			.parse::<Hotkey>()
+			.and_then(|hotkey| {
+				hotkey.listen(move || {
+					if let Err(e) = control::apply(Command::Acknowledge { title: None }, &sender) {
+						warn!("acknowledge from hotkey failed: {e}");
+					}
+				})
+			})
+			.inspect(|_| info!("acknowledge hotkey {hotkey} registered"))
+			.inspect_err(|e| warn!("There is a error when register hotkey {hotkey}: {e}"))
+			.unwrap_or(());
+	}
 	config
 		.report_methods
 		.iter()
-		.filter_map(|cfg| cfg.to_consumer())
+		.filter_map(|cfg| cfg.to_consumer(config.snapshot.as_ref()))
 		.for_each(|method| {
 			event_center
 				.add_consumer(method)
@@ -53,7 +88,7 @@ async fn entry_point() -> anyhow::Result<()> {
 	config
 		.characters
 		.into_iter()
-		.filter_map(|cfg| EveMonitor::new(cfg).ok())
+		.filter_map(|cfg| EveMonitor::new(cfg, config.snapshot.clone()).ok())
 		.for_each(|monitor| {
 			event_center
 				.add_producer(Box::new(monitor))
@@ -143,6 +178,8 @@ async fn main() -> anyhow::Result<()> {
 	}
 	if args.contains(&"--capture-only".to_string()) {
 		capture_only().await?;
+	} else if args.contains(&"--query".to_string()) {
+		event_log::query(&args, local_offset).await?;
 	} else {
 		entry_point().await?;
 	}
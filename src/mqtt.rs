@@ -0,0 +1,216 @@
+use crate::config::MqttConfig;
+use crate::delivery::Cooldown;
+use crate::event::{Event, EventConsumer};
+use crate::status;
+use crate::tts::{file_stem, render_template};
+use anyhow::anyhow;
+use rumqttc::{AsyncClient, LastWill, MqttOptions, Packet, QoS, Transport};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::sync::broadcast::Sender;
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tracing::{info, warn};
+
+const STATE_CHECK_SPACING: Duration = Duration::from_secs(1);
+const RECONNECT_SPACING: Duration = Duration::from_secs(5);
+
+fn character_topic(prefix: &str, title: &str) -> String {
+	format!(
+		"{prefix}/{}",
+		file_stem(&render_template("{character}", title))
+	)
+}
+
+/// `prefix/character/warn`, or `prefix/pause` for events without a character
+fn event_topic(prefix: &str, event: &Event) -> String {
+	let kind: &'static str = event.into();
+	match event.title() {
+		Some(title) => format!("{}/{}", character_topic(prefix, title), kind.to_lowercase()),
+		None => format!("{prefix}/{}", kind.to_lowercase()),
+	}
+}
+
+fn parse_qos(qos: u8) -> anyhow::Result<QoS> {
+	match qos {
+		0 => Ok(QoS::AtMostOnce),
+		1 => Ok(QoS::AtLeastOnce),
+		2 => Ok(QoS::ExactlyOnce),
+		_ => Err(anyhow!("invalid mqtt qos {qos}, expect 0, 1 or 2")),
+	}
+}
+
+/// rustls can't pick a crypto provider itself, reqwest and rumqttc bring aws-lc-rs besides ring
+fn tls_config() -> anyhow::Result<ClientConfig> {
+	let mut roots = RootCertStore::empty();
+	let native = rustls_native_certs::load_native_certs();
+	for e in &native.errors {
+		warn!("load platform certificates failed: {e}");
+	}
+	let (_, ignored) = roots.add_parsable_certificates(native.certs);
+	if ignored > 0 {
+		warn!("ignore {ignored} invalid platform certificates");
+	}
+	Ok(
+		ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+			.with_safe_default_protocol_versions()?
+			.with_root_certificates(roots)
+			.with_no_client_auth(),
+	)
+}
+
+fn options(config: &MqttConfig) -> anyhow::Result<MqttOptions> {
+	let url = &config.url;
+	let tls = match url.scheme() {
+		"mqtt" | "tcp" => false,
+		"mqtts" | "ssl" => true,
+		scheme => return Err(anyhow!("unsupported mqtt scheme {scheme}")),
+	};
+	let host = url
+		.host_str()
+		.ok_or_else(|| anyhow!("There is no host in mqtt url"))?;
+	let port = url.port().unwrap_or(if tls { 8883 } else { 1883 });
+	let mut options = MqttOptions::new(&config.client_id, host, port);
+	options.set_keep_alive(config.keep_alive);
+	options.set_last_will(LastWill::new(
+		format!("{}/status", config.topic_prefix),
+		"offline",
+		QoS::AtLeastOnce,
+		true,
+	));
+	if let Some(username) = &config.username {
+		options.set_credentials(username, config.password.clone().unwrap_or_default());
+	}
+	if tls {
+		options.set_transport(Transport::tls_with_config(tls_config()?.into()));
+	}
+	Ok(options)
+}
+
+pub struct MqttController {
+	sender: Option<Sender<Event>>,
+	config: MqttConfig,
+}
+
+impl MqttController {
+	pub fn new(config: MqttConfig) -> Self {
+		Self {
+			sender: None,
+			config,
+		}
+	}
+}
+
+impl EventConsumer for MqttController {
+	fn inject(&mut self, sender: Sender<Event>) {
+		self.sender = Some(sender)
+	}
+
+	fn start(&self) -> anyhow::Result<()> {
+		if self.sender.is_none() {
+			return Err(anyhow!("There is no sender"));
+		}
+		let mut receiver = self.sender.clone().unwrap().subscribe();
+		let qos = parse_qos(self.config.qos)?;
+		let prefix = self.config.topic_prefix.clone();
+		let mut cooldown = Cooldown::new(self.config.cooldown);
+		let (client, mut event_loop) = AsyncClient::new(options(&self.config)?, 64);
+		// retained topics are published again after every connect, the broker may have lost them
+		let connected = Arc::new(AtomicBool::new(false));
+
+		let connection_client = client.clone();
+		let connection_connected = connected.clone();
+		let status_topic = format!("{prefix}/status");
+		tokio::spawn(async move {
+			loop {
+				match event_loop.poll().await {
+					Ok(rumqttc::Event::Incoming(Packet::ConnAck(_))) => {
+						info!("mqtt connect successful");
+						let _ = connection_client.try_publish(&status_topic, QoS::AtLeastOnce, true, "online");
+						connection_connected.store(true, Ordering::Relaxed);
+					}
+					Ok(_) => {}
+					Err(e) => {
+						warn!("mqtt connection failed: {e}");
+						tokio::time::sleep(RECONNECT_SPACING).await;
+					}
+				}
+			}
+		});
+
+		tokio::spawn(async move {
+			let mut states = HashMap::<String, bool>::new();
+			let mut state_check = tokio::time::interval(STATE_CHECK_SPACING);
+			loop {
+				tokio::select! {
+					event = receiver.recv() => {
+						// the warn before it was published already
+						if let Ok(event) = event && !event.is_snapshot() && cooldown.allow(&event) {
+							let topic = event_topic(&prefix, &event);
+							let payload = serde_json::to_string(&event).unwrap();
+							if let Err(e) = client.try_publish(topic, qos, false, payload) {
+								warn!("mqtt publish failed: {e}");
+							}
+						}
+					}
+					_ = state_check.tick() => {
+						if connected.swap(false, Ordering::Relaxed) {
+							states.clear();
+						}
+						for character in status::get_status().characters {
+							if states.get(&character.title) == Some(&character.warning) {
+								continue;
+							}
+							let topic = format!("{}/state", character_topic(&prefix, &character.title));
+							let payload = if character.warning { "ON" } else { "OFF" };
+							if client.try_publish(topic, qos, true, payload).is_ok() {
+								states.insert(character.title, character.warning);
+							}
+						}
+					}
+				}
+			}
+		});
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::config::MqttConfig;
+	use crate::event::Event;
+	use crate::mqtt::{event_topic, options, parse_qos};
+	use rumqttc::Transport;
+	use tokio::test;
+
+	#[test]
+	async fn topics() {
+		let warn = Event::Warn {
+			title: "EVE - Char One".to_string(),
+		};
+		assert_eq!(event_topic("eve", &warn), "eve/Char_One/warn");
+		assert_eq!(
+			event_topic("eve", &Event::Pause { paused: true }),
+			"eve/pause"
+		);
+		assert!(parse_qos(2).is_ok());
+		assert!(parse_qos(3).is_err());
+	}
+
+	#[test]
+	async fn tls_options() {
+		let config = toml::from_str::<MqttConfig>(r#"url = "mqtts://broker.local""#).unwrap();
+		let tls = options(&config).unwrap();
+		assert_eq!(tls.broker_address(), ("broker.local".to_string(), 8883));
+		assert!(matches!(tls.transport(), Transport::Tls(_)));
+
+		let config = toml::from_str::<MqttConfig>(r#"url = "mqtt://broker.local""#).unwrap();
+		assert!(matches!(
+			options(&config).unwrap().transport(),
+			Transport::Tcp
+		));
+	}
+}
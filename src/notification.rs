@@ -33,6 +33,7 @@ impl EventConsumer for NotifyController {
 						Event::Reminder { title } => {
 							let _ = Notification::new().summary("Reminder").body(&title).show();
 						}
+						Event::Snapshot { .. } | Event::Snooze { .. } | Event::Pause { .. } => {}
 					}
 				}
 			}
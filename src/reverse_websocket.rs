@@ -1,28 +1,170 @@
-use crate::event::{Event, EventConsumer};
+use crate::config::ReverseWebsocketConfig;
+use crate::delivery::{backoff, header_map};
+use crate::event::{Command, Event, EventConsumer};
+use crate::status::RecordedEvent;
+use crate::{control, status};
 use anyhow::anyhow;
-use futures::SinkExt;
+use futures::{SinkExt, StreamExt};
+use std::collections::VecDeque;
 use std::time::Duration;
-use tokio::sync::broadcast::Sender;
+use tokio::net::TcpStream;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::{Receiver, Sender};
+use tokio::time::{Instant, MissedTickBehavior};
 use tokio_tungstenite::tungstenite::Message;
-use tracing::{info, warn};
-use url::Url;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::header::AUTHORIZATION;
+use tokio_tungstenite::tungstenite::http::{HeaderMap, HeaderValue};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tracing::{debug, info, warn};
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Buffers an event until it is sent, `capacity` only bounds the events kept while offline
+fn buffer_event(
+	buffer: &mut VecDeque<RecordedEvent>,
+	capacity: usize,
+	recorded: RecordedEvent,
+	online: bool,
+) {
+	buffer.push_back(recorded);
+	if !online {
+		trim_buffer(buffer, capacity);
+	}
+}
+
+/// Drops the oldest events beyond `capacity`
+fn trim_buffer(buffer: &mut VecDeque<RecordedEvent>, capacity: usize) {
+	while buffer.len() > capacity {
+		buffer.pop_front();
+	}
+}
+
+struct Connection {
+	sender: Sender<Event>,
+	config: ReverseWebsocketConfig,
+	headers: HeaderMap,
+	events: Receiver<RecordedEvent>,
+	buffer: VecDeque<RecordedEvent>,
+}
+
+impl Connection {
+	fn receive(&mut self, recorded: Result<RecordedEvent, RecvError>, online: bool) {
+		match recorded {
+			Ok(recorded) => buffer_event(
+				&mut self.buffer,
+				self.config.offline_buffer,
+				recorded,
+				online,
+			),
+			Err(RecvError::Lagged(count)) => warn!("reverse websocket dropped {count} events"),
+			Err(RecvError::Closed) => {}
+		}
+	}
+
+	async fn connect(&self) -> anyhow::Result<WsStream> {
+		info!("try to connect {}", self.config.url);
+		let mut request = self.config.url.as_str().into_client_request()?;
+		request.headers_mut().extend(self.headers.clone());
+		let (ws_stream, _) = tokio_tungstenite::connect_async(request).await?;
+		info!("reverse websocket connect successful");
+		Ok(ws_stream)
+	}
+
+	/// Sends buffered events in order, an event stays buffered until its send succeeds
+	async fn flush(&mut self, ws_stream: &mut WsStream) -> anyhow::Result<()> {
+		while let Some(recorded) = self.buffer.front() {
+			match serde_json::to_string(recorded) {
+				Ok(data) => ws_stream.send(Message::Text(data.into())).await?,
+				Err(e) => warn!("event serialize failed: {}", e),
+			}
+			self.buffer.pop_front();
+		}
+		Ok(())
+	}
+
+	async fn run(&mut self, mut ws_stream: WsStream) -> anyhow::Result<()> {
+		if !self.buffer.is_empty() {
+			info!("send {} events buffered while offline", self.buffer.len());
+		}
+		self.flush(&mut ws_stream).await?;
+		let heartbeat = self.config.heartbeat;
+		let mut heartbeat_interval = tokio::time::interval_at(Instant::now() + heartbeat, heartbeat);
+		heartbeat_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		let mut last_received = Instant::now();
+		loop {
+			tokio::select! {
+				recorded = self.events.recv() => {
+					self.receive(recorded, true);
+					self.flush(&mut ws_stream).await?;
+				}
+				_ = heartbeat_interval.tick() => {
+					if last_received.elapsed() > heartbeat * 2 {
+						return Err(anyhow!("no heartbeat from server in {:?}", heartbeat * 2));
+					}
+					ws_stream.send(Message::Ping(Default::default())).await?;
+				}
+				message = ws_stream.next() => {
+					last_received = Instant::now();
+					match message {
+						Some(Ok(Message::Text(text))) => match serde_json::from_str::<Command>(&text) {
+							Ok(command) => {
+								if let Err(e) = control::apply(command, &self.sender) {
+									warn!("apply command from server failed: {e}");
+								}
+							}
+							Err(e) => warn!("unknown command {}: {}", text, e),
+						},
+						// the pong is queued by tungstenite, flush sends it right away
+						Some(Ok(Message::Ping(_))) => ws_stream.flush().await?,
+						Some(Ok(Message::Close(frame))) => {
+							return Err(anyhow!("connection closed by server: {:?}", frame));
+						}
+						Some(Ok(message)) => debug!(?message, "ignore websocket message"),
+						Some(Err(e)) => return Err(e.into()),
+						None => return Err(anyhow!("connection closed by server")),
+					}
+				}
+			}
+		}
+	}
+
+	/// Keeps buffering events while waiting for the next attempt
+	async fn wait(&mut self, delay: Duration) {
+		let sleep = tokio::time::sleep(delay);
+		tokio::pin!(sleep);
+		loop {
+			tokio::select! {
+				_ = &mut sleep => return,
+				recorded = self.events.recv() => self.receive(recorded, false),
+			}
+		}
+	}
+}
 
 pub struct ReverseWebsocketController {
 	sender: Option<Sender<Event>>,
-	url: Url,
-	try_forever: bool,
-	try_spacing: Duration,
+	config: ReverseWebsocketConfig,
 }
 
 impl ReverseWebsocketController {
-	pub fn new(url: Url, try_forever: bool, try_spacing: Duration) -> Self {
+	pub fn new(config: ReverseWebsocketConfig) -> Self {
 		Self {
-			url,
 			sender: None,
-			try_forever,
-			try_spacing,
+			config,
 		}
 	}
+
+	fn headers(&self) -> anyhow::Result<HeaderMap> {
+		let mut headers = header_map(&self.config.headers)?;
+		if let Some(token) = &self.config.token {
+			headers.insert(
+				AUTHORIZATION,
+				HeaderValue::from_str(&format!("Bearer {token}"))?,
+			);
+		}
+		Ok(headers)
+	}
 }
 
 impl EventConsumer for ReverseWebsocketController {
@@ -34,40 +176,152 @@ impl EventConsumer for ReverseWebsocketController {
 		if self.sender.is_none() {
 			return Err(anyhow!("There is no sender"));
 		}
-		let sender = self.sender.clone().unwrap();
-		let url = self.url.clone();
-		let try_forever = self.try_forever;
-		let try_spacing = self.try_spacing;
+		// subscribed once for the whole lifetime, so events between connections are buffered
+		let mut connection = Connection {
+			sender: self.sender.clone().unwrap(),
+			config: self.config.clone(),
+			headers: self.headers()?,
+			events: status::subscribe(),
+			buffer: VecDeque::new(),
+		};
 		tokio::spawn(async move {
-			let task = async || {
-				info!("try to connect {}", url);
-				let mut receiver = sender.subscribe();
-				let (mut ws_stream, _) = tokio_tungstenite::connect_async(url.to_string()).await?;
-				info!("reverse websocket connect successful");
-				loop {
-					if let Ok(event) = receiver.recv().await {
-						match serde_json::to_string(&event) {
-							Ok(data) => {
-								ws_stream.send(Message::Text(data.into())).await?;
-							}
-							Err(e) => warn!("event serialize failed: {}", e),
-						}
+			let mut attempt = 0;
+			loop {
+				let result = match connection.connect().await {
+					Ok(ws_stream) => {
+						attempt = 0;
+						connection.run(ws_stream).await
 					}
+					Err(e) => Err(e),
+				};
+				let _ = result.inspect_err(|e| warn!("cannot connect ws server: {}", e));
+				// a live event whose send failed is now an offline one
+				trim_buffer(&mut connection.buffer, connection.config.offline_buffer);
+				if !connection.config.try_forever {
+					break;
 				}
-			};
+				let delay = backoff(
+					connection.config.try_spacing,
+					connection.config.max_try_spacing,
+					attempt,
+				);
+				attempt = attempt.saturating_add(1);
+				info!("reconnect in {:?}", delay);
+				connection.wait(delay).await;
+			}
+		});
 
-			if try_forever {
-				loop {
-					let result: anyhow::Result<()> = task().await;
-					let _ = result.inspect_err(|e| warn!("cannot connect ws server: {}", e));
-					tokio::time::sleep(try_spacing).await;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::config::ReverseWebsocketConfig;
+	use crate::event::Event;
+	use crate::reverse_websocket::{Connection, buffer_event, trim_buffer};
+	use crate::status::{self, RecordedEvent};
+	use futures::{SinkExt, StreamExt};
+	use std::collections::{HashMap, VecDeque};
+	use std::time::Duration;
+	use tokio::net::TcpListener;
+	use tokio::sync::broadcast;
+	use tokio::test;
+	use tokio_tungstenite::tungstenite::Message;
+
+	async fn local_connection(heartbeat: Duration) -> (Connection, TcpListener) {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let config = ReverseWebsocketConfig {
+			url: format!("ws://{}/ws", listener.local_addr().unwrap())
+				.parse()
+				.unwrap(),
+			try_forever: false,
+			try_spacing: Duration::ZERO,
+			max_try_spacing: Duration::ZERO,
+			offline_buffer: 0,
+			token: None,
+			headers: HashMap::new(),
+			heartbeat,
+		};
+		let connection = Connection {
+			sender: broadcast::channel(1).0,
+			config,
+			headers: Default::default(),
+			events: status::subscribe(),
+			buffer: VecDeque::new(),
+		};
+		(connection, listener)
+	}
+
+	#[test]
+	async fn heartbeat() {
+		// a server that stops reading never answers the pings
+		let (mut connection, listener) = local_connection(Duration::from_millis(50)).await;
+		let server = tokio::spawn(async move {
+			let (stream, _) = listener.accept().await.unwrap();
+			let ws_stream = tokio_tungstenite::accept_async(stream).await.unwrap();
+			tokio::time::sleep(Duration::from_secs(5)).await;
+			drop(ws_stream);
+		});
+		let ws_stream = connection.connect().await.unwrap();
+		let result = tokio::time::timeout(Duration::from_secs(2), connection.run(ws_stream))
+			.await
+			.unwrap();
+		assert!(result.unwrap_err().to_string().contains("no heartbeat"));
+		server.abort();
+
+		// pings of the server are answered right away
+		let (mut connection, listener) = local_connection(Duration::from_secs(30)).await;
+		let server = tokio::spawn(async move {
+			let (stream, _) = listener.accept().await.unwrap();
+			let mut ws_stream = tokio_tungstenite::accept_async(stream).await.unwrap();
+			ws_stream
+				.send(Message::Ping(b"hi".to_vec().into()))
+				.await
+				.unwrap();
+			while let Some(Ok(message)) = ws_stream.next().await {
+				if let Message::Pong(payload) = message {
+					return payload;
 				}
-			} else {
-				let result: anyhow::Result<()> = task().await;
-				let _ = result.inspect_err(|e| warn!("cannot connect ws server: {}", e));
 			}
+			panic!("no pong");
 		});
+		let ws_stream = connection.connect().await.unwrap();
+		let client = tokio::spawn(async move { connection.run(ws_stream).await });
+		let payload = tokio::time::timeout(Duration::from_secs(2), server)
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(&payload[..], b"hi");
+		client.abort();
+	}
 
-		Ok(())
+	#[test]
+	async fn offline_buffer() {
+		let recorded = |id| RecordedEvent {
+			id,
+			at: id,
+			event: Event::Pause { paused: true },
+		};
+		let ids = |buffer: &VecDeque<RecordedEvent>| {
+			buffer
+				.iter()
+				.map(|recorded| recorded.id)
+				.collect::<Vec<_>>()
+		};
+		let mut buffer = VecDeque::new();
+		for id in 0..5 {
+			buffer_event(&mut buffer, 3, recorded(id), false);
+		}
+		assert_eq!(ids(&buffer), [2, 3, 4]);
+
+		// no offline buffer still passes live events on
+		let mut buffer = VecDeque::new();
+		buffer_event(&mut buffer, 0, recorded(0), false);
+		assert!(buffer.is_empty());
+		buffer_event(&mut buffer, 0, recorded(1), true);
+		assert_eq!(ids(&buffer), [1]);
+		trim_buffer(&mut buffer, 0);
+		assert!(buffer.is_empty());
 	}
 }
@@ -0,0 +1,176 @@
+use crate::config::{Region, SnapshotConfig};
+use crate::tts::file_stem;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use image::{ImageFormat, RgbaImage, imageops};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use time::OffsetDateTime;
+use time::macros::format_description;
+use tracing::{debug, warn};
+
+/// The frame that triggered a warn, saved on disk
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+	/// Only meaningful on this machine, so it is not serialized
+	#[serde(skip)]
+	pub path: PathBuf,
+	/// Path under the snapshot dir, the sse server serves the file at `/snapshots/<name>`
+	pub name: String,
+	/// Base64 encoded png, absent when `thumbnail_width` is 0
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub thumbnail: Option<String>,
+}
+
+/// Cuts the warn region plus `padding` out of the frame, clamped to the frame size
+pub fn crop(frame: &RgbaImage, region: &Region, padding: u32) -> RgbaImage {
+	let (width, height) = frame.dimensions();
+	let x = region.start[0].saturating_sub(padding).min(width);
+	let y = region.start[1].saturating_sub(padding).min(height);
+	let end_x = region.end[0].saturating_add(padding + 1).min(width);
+	let end_y = region.end[1].saturating_add(padding + 1).min(height);
+	imageops::crop_imm(
+		frame,
+		x,
+		y,
+		end_x.saturating_sub(x),
+		end_y.saturating_sub(y),
+	)
+	.to_image()
+}
+
+fn encode_png(image: &RgbaImage) -> anyhow::Result<Vec<u8>> {
+	let mut png = Vec::new();
+	image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+	Ok(png)
+}
+
+/// Saves the snapshot of a warn to `dir/YYYY-MM-DD/HH-MM-SS.mmm-character.png`, blocking
+pub fn capture(
+	config: &SnapshotConfig,
+	title: &str,
+	region: &Region,
+	frame: &RgbaImage,
+) -> anyhow::Result<Snapshot> {
+	let image = if config.full_frame {
+		frame.clone()
+	} else {
+		crop(frame, region, config.padding)
+	};
+	let now = OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc());
+	let day = now.format(format_description!("[year]-[month]-[day]"))?;
+	let dir = config.dir.join(&day);
+	std::fs::create_dir_all(&dir)?;
+	let time = now.format(format_description!(
+		"[hour]-[minute]-[second].[subsecond digits:3]"
+	))?;
+	let character = title.strip_prefix("EVE - ").unwrap_or(title);
+	let file_name = format!("{time}-{}.png", file_stem(character));
+	let path = dir.join(&file_name);
+	image.save(&path)?;
+	debug!(?path, "snapshot saved");
+
+	let thumbnail = Some(config.thumbnail_width)
+		.filter(|width| *width > 0)
+		.map(|width| {
+			let width = width.min(image.width()).max(1);
+			let height = (image.height() * width / image.width().max(1)).max(1);
+			encode_png(&imageops::thumbnail(&image, width, height))
+		})
+		.transpose()?
+		.map(|png| STANDARD.encode(png));
+
+	if let Err(e) = prune(config) {
+		warn!("prune snapshots failed: {e}");
+	}
+	Ok(Snapshot {
+		path,
+		name: format!("{day}/{file_name}"),
+		thumbnail,
+	})
+}
+
+/// Removes snapshots beyond `max_files` or older than `max_age`, and the emptied day folders
+fn prune(config: &SnapshotConfig) -> anyhow::Result<()> {
+	let mut days = std::fs::read_dir(&config.dir)?
+		.filter_map(Result::ok)
+		.map(|entry| entry.path())
+		.filter(|path| path.is_dir())
+		.collect::<Vec<_>>();
+	days.sort();
+	let mut files = Vec::new();
+	for day in &days {
+		let mut day_files = std::fs::read_dir(day)?
+			.filter_map(Result::ok)
+			.map(|entry| entry.path())
+			.filter(|path| path.extension().is_some_and(|extension| extension == "png"))
+			.collect::<Vec<_>>();
+		day_files.sort();
+		files.append(&mut day_files);
+	}
+
+	let expired = files.len().saturating_sub(config.max_files);
+	let now = SystemTime::now();
+	for (index, file) in files.iter().enumerate() {
+		let too_old = config.max_age.is_some_and(|max_age| {
+			std::fs::metadata(file)
+				.and_then(|metadata| metadata.modified())
+				.is_ok_and(|modified| now.duration_since(modified).unwrap_or_default() > max_age)
+		});
+		if index < expired || too_old {
+			std::fs::remove_file(file)?;
+		}
+	}
+	for day in days {
+		if is_empty_dir(&day) {
+			let _ = std::fs::remove_dir(day);
+		}
+	}
+	Ok(())
+}
+
+fn is_empty_dir(path: &Path) -> bool {
+	std::fs::read_dir(path).is_ok_and(|mut entries| entries.next().is_none())
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::config::{Region, SnapshotConfig};
+	use crate::snapshot::{capture, crop};
+	use image::RgbaImage;
+
+	#[test]
+	fn crop_and_retention() {
+		let frame = RgbaImage::new(100, 80);
+		let region = Region {
+			start: [10, 70],
+			end: [20, 75],
+			rgb: Vec::new(),
+		};
+		assert_eq!(crop(&frame, &region, 5).dimensions(), (21, 15));
+		assert_eq!(crop(&frame, &region, 0).dimensions(), (11, 6));
+
+		let dir = std::env::temp_dir().join(format!("snapshot-{}", std::process::id()));
+		let config = SnapshotConfig {
+			dir: dir.clone(),
+			full_frame: false,
+			padding: 5,
+			max_files: 2,
+			max_age: None,
+			thumbnail_width: 8,
+		};
+		let snapshots = (0..4)
+			.map(|_| {
+				std::thread::sleep(std::time::Duration::from_millis(2));
+				capture(&config, "EVE - CHAR1", &region, &frame).unwrap()
+			})
+			.collect::<Vec<_>>();
+		assert!(snapshots[3].path.exists());
+		assert!(!snapshots[0].path.exists());
+		assert!(snapshots[3].thumbnail.is_some());
+		assert_eq!(dir.join(&snapshots[3].name), snapshots[3].path);
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+}
@@ -1,51 +1,326 @@
-use crate::config::{Host, Port};
-use crate::event::{Event as ReportEvent, EventConsumer};
-use crate::get_char_titles;
+use crate::config::TlsConfig;
+use crate::event::{Command, Event as ReportEvent, EventConsumer};
+use crate::status::{RecordedEvent, Status};
+use crate::{control, get_char_titles, get_config, status};
 use anyhow::anyhow;
-use axum::extract::State;
-use axum::response::Sse;
+use axum::Json;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path as AxumPath, Query, Request, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::middleware::Next;
 use axum::response::sse::Event;
-use axum::routing::get;
+use axum::response::{IntoResponse, Response, Sse};
+use axum::routing::{get, post};
 use futures::Stream;
 use futures::StreamExt;
-use futures::stream;
+use futures::{future, stream};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use subtle::ConstantTimeEq;
+use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::broadcast::Sender;
+use tokio::sync::mpsc;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::server::TlsStream;
 use tokio_stream::wrappers::BroadcastStream;
-use tower_http::cors::{Any, CorsLayer};
-use tracing::info;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tracing::{debug, info, warn};
 
-async fn sse_handler(
-	State(sender): State<Sender<ReportEvent>>,
-) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
-	let receiver = sender.subscribe();
-	let broadcast_stream = BroadcastStream::new(receiver).map(|msg| match msg {
-		Ok(data) => Ok(Event::default().data(serde_json::to_string(&data).unwrap())),
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn to_sse_event(recorded: &RecordedEvent) -> Event {
+	Event::default()
+		.id(recorded.id.to_string())
+		.data(serde_json::to_string(recorded).unwrap())
+}
+
+/// Replays the history after `last_id` then follows live events
+fn recorded_events(
+	last_id: Option<u64>,
+) -> impl Stream<Item = Result<RecordedEvent, BroadcastStreamRecvError>> {
+	// subscribe before reading the history, so nothing falls between replay and live events
+	let receiver = status::subscribe();
+	let replay = last_id.map(status::get_history_since).unwrap_or_default();
+	let replayed_until = replay.last().map_or(0, |recorded| recorded.id);
+	debug!(replay = replay.len(), "replay history");
+
+	let live = BroadcastStream::new(receiver).filter(move |msg| {
+		future::ready(!matches!(msg, Ok(recorded) if recorded.id <= replayed_until))
+	});
+	stream::iter(replay.into_iter().map(Ok)).chain(live)
+}
+
+async fn sse_handler(headers: HeaderMap) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+	let last_id = headers
+		.get("last-event-id")
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.parse::<u64>().ok());
+	let recorded_stream = recorded_events(last_id).map(|msg| match msg {
+		Ok(recorded) => Ok(to_sse_event(&recorded)),
 		Err(_) => Err(axum::Error::new("broadcast error")),
 	});
 
 	let initial_event =
 		async move { Ok(Event::default().data(serde_json::to_string(&get_char_titles()).unwrap())) };
 
-	let stream = stream::once(initial_event).chain(broadcast_stream);
-
 	info!("create sse connection successful");
 
+	let stream = stream::once(initial_event).chain(recorded_stream);
+
 	Sse::new(stream)
 		.keep_alive(axum::response::sse::KeepAlive::new().interval(std::time::Duration::from_secs(5)))
 }
 
+#[derive(Debug, Deserialize)]
+struct WsQuery {
+	last_event_id: Option<u64>,
+}
+
+/// Websocket messages answered by the server itself instead of `control::apply`
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+enum WsRequest {
+	Ping,
+}
+
+async fn ws_handler(
+	ws: WebSocketUpgrade,
+	State(sender): State<Sender<ReportEvent>>,
+	Query(query): Query<WsQuery>,
+) -> Response {
+	ws.on_upgrade(move |socket| ws_session(socket, sender, query.last_event_id))
+}
+
+async fn ws_session(mut socket: WebSocket, sender: Sender<ReportEvent>, last_id: Option<u64>) {
+	info!("create websocket connection successful");
+	let mut events = Box::pin(recorded_events(last_id));
+	let titles = serde_json::to_string(&get_char_titles()).unwrap();
+	if socket.send(Message::Text(titles.into())).await.is_err() {
+		return;
+	}
+
+	loop {
+		tokio::select! {
+			recorded = events.next() => match recorded {
+				Some(Ok(recorded)) => {
+					let text = serde_json::to_string(&recorded).unwrap();
+					if socket.send(Message::Text(text.into())).await.is_err() {
+						break;
+					}
+				}
+				Some(Err(e)) => warn!("websocket client missed events: {e}"),
+				None => break,
+			},
+			msg = socket.recv() => match msg {
+				Some(Ok(Message::Text(text))) => {
+					if let Ok(command) = serde_json::from_str::<Command>(&text) {
+						info!(?command, "receive command from websocket client");
+						if let Err(e) = control::apply(command, &sender) {
+							warn!("apply command from websocket client failed: {e}");
+						}
+					} else if let Ok(WsRequest::Ping) = serde_json::from_str::<WsRequest>(&text) {
+						if socket.send(Message::Text(r#"{"type":"Pong"}"#.into())).await.is_err() {
+							break;
+						}
+					} else {
+						warn!("unknown websocket message: {text}");
+					}
+				}
+				Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+				Some(Ok(_)) => {}
+			},
+		}
+	}
+	info!("websocket connection closed");
+}
+
+async fn command_handler(
+	State(sender): State<Sender<ReportEvent>>,
+	Json(command): Json<Command>,
+) -> Result<StatusCode, (StatusCode, String)> {
+	info!(?command, "receive command from sse client");
+	control::apply(command, &sender).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+	Ok(StatusCode::NO_CONTENT)
+}
+
+async fn status_handler() -> Json<Status> {
+	Json(status::get_status())
+}
+
+async fn history_handler() -> Json<Vec<RecordedEvent>> {
+	Json(status::get_history())
+}
+
+async fn config_handler() -> impl IntoResponse {
+	match get_config() {
+		Some(config) => Json(config).into_response(),
+		None => StatusCode::NOT_FOUND.into_response(),
+	}
+}
+
+/// CORS doesn't cover websocket upgrades, a browser page may only open `/ws` from a listed origin.
+/// Clients outside a browser send no `Origin` and pass
+fn is_origin_allowed(request: &Request, allowed_origins: &[String]) -> bool {
+	match request.headers().get(header::ORIGIN) {
+		Some(origin) => allowed_origins
+			.iter()
+			.any(|allowed| origin == allowed.as_str()),
+		None => true,
+	}
+}
+
+/// Serves a saved snapshot, `name` is its path under the snapshot dir
+async fn snapshot_handler(dir: &Path, name: &str) -> Response {
+	// only the day folder and file, nothing outside the snapshot dir
+	let inside = Path::new(name)
+		.components()
+		.all(|component| matches!(component, Component::Normal(_)));
+	if !inside || !name.ends_with(".png") {
+		return StatusCode::NOT_FOUND.into_response();
+	}
+	match tokio::fs::read(dir.join(name)).await {
+		Ok(png) => ([(header::CONTENT_TYPE, "image/png")], png).into_response(),
+		Err(_) => StatusCode::NOT_FOUND.into_response(),
+	}
+}
+
+/// Accepts `Authorization: Bearer <token>` or a `?token=<token>` query, browsers can't set headers on EventSource
+fn is_authorized(request: &Request, token: &str) -> bool {
+	let bearer = request
+		.headers()
+		.get(header::AUTHORIZATION)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.strip_prefix("Bearer "));
+	let query = request.uri().query().and_then(|query| {
+		url::form_urlencoded::parse(query.as_bytes())
+			.find(|(key, _)| key == "token")
+			.map(|(_, value)| value.into_owned())
+	});
+	// compared in constant time, only the token length leaks
+	let matches = |given: &str| bool::from(given.as_bytes().ct_eq(token.as_bytes()));
+	bearer.is_some_and(matches) || query.as_deref().is_some_and(matches)
+}
+
+fn cors_layer(allowed_origins: &[String]) -> anyhow::Result<CorsLayer> {
+	let allow_origin = if allowed_origins.is_empty() {
+		AllowOrigin::from(Any)
+	} else {
+		AllowOrigin::list(
+			allowed_origins
+				.iter()
+				.map(|origin| {
+					HeaderValue::from_str(origin).map_err(|e| anyhow!("invalid origin {origin}: {e}"))
+				})
+				.collect::<anyhow::Result<Vec<_>>>()?,
+		)
+	};
+	Ok(
+		CorsLayer::new()
+			.allow_origin(allow_origin)
+			.allow_methods(Any)
+			.allow_headers(Any),
+	)
+}
+
+fn load_tls(tls: &TlsConfig) -> anyhow::Result<TlsAcceptor> {
+	let certs = CertificateDer::pem_file_iter(&tls.cert_path)
+		.and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+		.map_err(|e| anyhow!("read certificate {:?} failed: {e}", tls.cert_path))?;
+	let key = PrivateKeyDer::from_pem_file(&tls.key_path)
+		.map_err(|e| anyhow!("read private key {:?} failed: {e}", tls.key_path))?;
+	// reqwest enables a second rustls crypto provider, so the process wide default can't be picked
+	let config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+		.with_safe_default_protocol_versions()?
+		.with_no_client_auth()
+		.with_single_cert(certs, key)?;
+	Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Runs every handshake in its own task, so a slow client doesn't hold up the others
+struct TlsListener {
+	handshaken: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+	local_addr: SocketAddr,
+}
+
+impl TlsListener {
+	fn new(mut listener: TcpListener, acceptor: TlsAcceptor) -> std::io::Result<Self> {
+		let local_addr = listener.local_addr()?;
+		let (sender, handshaken) = mpsc::channel(16);
+		tokio::spawn(async move {
+			while !sender.is_closed() {
+				let (stream, addr) = axum::serve::Listener::accept(&mut listener).await;
+				let acceptor = acceptor.clone();
+				let sender = sender.clone();
+				tokio::spawn(async move {
+					match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+						Ok(Ok(stream)) => {
+							let _ = sender.send((stream, addr)).await;
+						}
+						Ok(Err(e)) => debug!("tls handshake with {addr} failed: {e}"),
+						Err(_) => debug!("tls handshake with {addr} timed out"),
+					}
+				});
+			}
+		});
+		Ok(Self {
+			handshaken,
+			local_addr,
+		})
+	}
+}
+
+impl axum::serve::Listener for TlsListener {
+	type Io = TlsStream<TcpStream>;
+	type Addr = SocketAddr;
+
+	async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+		match self.handshaken.recv().await {
+			Some(accepted) => accepted,
+			// the accept loop only ends once this listener is dropped
+			None => std::future::pending().await,
+		}
+	}
+
+	fn local_addr(&self) -> std::io::Result<Self::Addr> {
+		Ok(self.local_addr)
+	}
+}
+
 pub struct SseServerController {
 	sender: Option<Sender<ReportEvent>>,
-	host: Host,
-	port: Port,
+	addresses: Vec<String>,
+	token: Option<String>,
+	allowed_origins: Vec<String>,
+	tls: Option<TlsConfig>,
+	websocket: bool,
+	snapshot_dir: Option<PathBuf>,
 }
 
 impl SseServerController {
-	pub fn new(host: Host, port: Port) -> Self {
+	pub fn new(
+		addresses: Vec<String>,
+		token: Option<String>,
+		allowed_origins: Vec<String>,
+		tls: Option<TlsConfig>,
+		websocket: bool,
+		snapshot_dir: Option<PathBuf>,
+	) -> Self {
 		Self {
 			sender: None,
-			host,
-			port,
+			addresses,
+			token,
+			allowed_origins,
+			tls,
+			websocket,
+			snapshot_dir,
 		}
 	}
 }
@@ -61,24 +336,241 @@ impl EventConsumer for SseServerController {
 		}
 		let sender = self.sender.clone().unwrap();
 
-		let host = self.host;
-		let port = self.port;
+		let cors = cors_layer(&self.allowed_origins)?;
+		let acceptor = self.tls.as_ref().map(load_tls).transpose()?;
+		let token = self.token.clone();
 
-		tokio::spawn(async move {
-			let addr = format!("{}.{}.{}.{}:{}", host[0], host[1], host[2], host[3], port);
-			let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-			let app = axum::Router::new()
-				.route("/events", get(sse_handler))
-				.with_state(sender)
-				.layer(
-					CorsLayer::new()
-						.allow_origin(Any)
-						.allow_methods(Any)
-						.allow_headers(Any),
-				);
-			info!("SSE server run on {}", addr);
-			axum::serve(listener, app).await.unwrap();
-		});
+		let mut app = axum::Router::new()
+			.route("/events", get(sse_handler))
+			.route("/command", post(command_handler))
+			.route("/status", get(status_handler))
+			.route("/history", get(history_handler))
+			.route("/config", get(config_handler));
+		if let Some(dir) = self.snapshot_dir.clone() {
+			app = app.route(
+				"/snapshots/{*name}",
+				get(
+					move |AxumPath(name): AxumPath<String>| async move { snapshot_handler(&dir, &name).await },
+				),
+			);
+		}
+		if self.websocket {
+			let allowed_origins = self.allowed_origins.clone();
+			let origin_check = axum::middleware::from_fn(move |request: Request, next: Next| {
+				let allowed = is_origin_allowed(&request, &allowed_origins);
+				async move {
+					if allowed {
+						next.run(request).await
+					} else {
+						StatusCode::FORBIDDEN.into_response()
+					}
+				}
+			});
+			app = app.route("/ws", get(ws_handler).layer(origin_check));
+		}
+		let mut app = app.with_state(sender);
+		if let Some(token) = token {
+			app = app.layer(axum::middleware::from_fn(
+				move |request: Request, next: Next| {
+					let authorized = is_authorized(&request, &token);
+					async move {
+						if authorized {
+							next.run(request).await
+						} else {
+							StatusCode::UNAUTHORIZED.into_response()
+						}
+					}
+				},
+			));
+		}
+		// cors wraps the auth layer so preflight requests without a token pass
+		let app = app.layer(cors);
+
+		// bind before spawning, so a port in use fails the start instead of a detached task
+		let listeners = self
+			.addresses
+			.iter()
+			.map(|addr| {
+				let listener = std::net::TcpListener::bind(addr)
+					.map_err(|e| anyhow!("SSE server bind {addr} failed: {e}"))?;
+				listener.set_nonblocking(true)?;
+				Ok((addr.clone(), TcpListener::from_std(listener)?))
+			})
+			.collect::<anyhow::Result<Vec<_>>>()?;
+
+		for (addr, listener) in listeners {
+			let app = app.clone();
+			let acceptor = acceptor.clone();
+			tokio::spawn(async move {
+				let result = match acceptor {
+					Some(acceptor) => {
+						info!("SSE server run on https://{}", addr);
+						match TlsListener::new(listener, acceptor) {
+							Ok(listener) => axum::serve(listener, app).await,
+							Err(e) => Err(e),
+						}
+					}
+					None => {
+						info!("SSE server run on {}", addr);
+						axum::serve(listener, app).await
+					}
+				};
+				if let Err(e) = result {
+					warn!("SSE server on {addr} stopped: {e}");
+				}
+			});
+		}
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use crate::config::TlsConfig;
+	use crate::event::EventConsumer;
+	use crate::sse::{SseServerController, TlsListener, load_tls};
+	use axum::serve::Listener;
+	use std::sync::Arc;
+	use std::time::Duration;
+	use tokio::net::{TcpListener, TcpStream};
+	use tokio::sync::broadcast;
+	use tokio::test;
+	use tokio_rustls::TlsConnector;
+	use tokio_rustls::rustls::crypto::ring;
+	use tokio_rustls::rustls::pki_types::ServerName;
+	use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+	use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+	#[test]
+	async fn bind_failure() {
+		let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let addr = taken.local_addr().unwrap().to_string();
+		let mut controller = SseServerController::new(vec![addr], None, Vec::new(), None, false, None);
+		controller.inject(broadcast::channel(1).0);
+		assert!(controller.start().is_err());
+	}
+
+	#[test]
+	async fn tls_handshake() {
+		let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+		let dir = std::env::temp_dir().join(format!("sse-tls-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let tls = TlsConfig {
+			cert_path: dir.join("cert.pem"),
+			key_path: dir.join("key.pem"),
+		};
+		std::fs::write(&tls.cert_path, certified.cert.pem()).unwrap();
+		std::fs::write(&tls.key_path, certified.signing_key.serialize_pem()).unwrap();
+		let acceptor = load_tls(&tls).unwrap();
+		std::fs::remove_dir_all(&dir).unwrap();
+
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let mut listener = TlsListener::new(listener, acceptor).unwrap();
+		let addr = listener.local_addr().unwrap();
+
+		// a client that never starts its handshake must not hold up the next one
+		let _stalled = TcpStream::connect(addr).await.unwrap();
+
+		let mut roots = RootCertStore::empty();
+		roots.add(certified.cert.der().clone()).unwrap();
+		let client_config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+			.with_safe_default_protocol_versions()
+			.unwrap()
+			.with_root_certificates(roots)
+			.with_no_client_auth();
+		let connector = TlsConnector::from(Arc::new(client_config));
+		let client = tokio::spawn(async move {
+			let stream = TcpStream::connect(addr).await.unwrap();
+			connector
+				.connect(ServerName::try_from("localhost").unwrap(), stream)
+				.await
+				.unwrap()
+		});
+		tokio::time::timeout(Duration::from_secs(5), listener.accept())
+			.await
+			.unwrap();
+		client.await.unwrap();
+	}
+
+	#[test]
+	async fn websocket_origin() {
+		let addr = std::net::TcpListener::bind("127.0.0.1:0")
+			.unwrap()
+			.local_addr()
+			.unwrap();
+		let mut controller = SseServerController::new(
+			vec![addr.to_string()],
+			None,
+			vec!["http://localhost:1420".to_string()],
+			None,
+			true,
+			None,
+		);
+		controller.inject(broadcast::channel(1).0);
+		controller.start().unwrap();
+
+		let connect = |origin: Option<&'static str>| {
+			let mut request = format!("ws://{addr}/ws").into_client_request().unwrap();
+			if let Some(origin) = origin {
+				request
+					.headers_mut()
+					.insert("origin", origin.parse().unwrap());
+			}
+			tokio_tungstenite::connect_async(request)
+		};
+		assert!(connect(None).await.is_ok());
+		assert!(connect(Some("http://localhost:1420")).await.is_ok());
+		assert!(connect(Some("https://evil.example")).await.is_err());
+	}
+
+	#[test]
+	async fn serve_snapshots() {
+		let dir = std::env::temp_dir().join(format!("sse-snapshots-{}", std::process::id()));
+		std::fs::create_dir_all(dir.join("2026-01-01")).unwrap();
+		std::fs::write(dir.join("2026-01-01/00-00-00.000-CHAR1.png"), b"png").unwrap();
+		let addr = std::net::TcpListener::bind("127.0.0.1:0")
+			.unwrap()
+			.local_addr()
+			.unwrap();
+		let mut controller = SseServerController::new(
+			vec![addr.to_string()],
+			Some("token".to_string()),
+			Vec::new(),
+			None,
+			false,
+			Some(dir.clone()),
+		);
+		controller.inject(broadcast::channel(1).0);
+		controller.start().unwrap();
+
+		let get = |name: &str| {
+			reqwest::Client::new()
+				.get(format!("http://{addr}/snapshots/{name}"))
+				.bearer_auth("token")
+				.send()
+		};
+		let response = get("2026-01-01/00-00-00.000-CHAR1.png").await.unwrap();
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.headers()["content-type"], "image/png");
+		assert_eq!(&response.bytes().await.unwrap()[..], b"png");
+		assert_eq!(get("2026-01-01/missing.png").await.unwrap().status(), 404);
+		assert_eq!(get("..%2Fsecret.png").await.unwrap().status(), 404);
+		let unauthorized = reqwest::get(format!(
+			"http://{addr}/snapshots/2026-01-01/00-00-00.000-CHAR1.png"
+		))
+		.await
+		.unwrap();
+		assert_eq!(unauthorized.status(), 401);
+		let url = format!("http://{addr}/snapshots/2026-01-01/00-00-00.000-CHAR1.png");
+		let client = reqwest::Client::new();
+		let wrong = client.get(&url).bearer_auth("tokem").send().await.unwrap();
+		assert_eq!(wrong.status(), 401);
+		let query = client
+			.get(format!("{url}?token=token"))
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(query.status(), 200);
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+}
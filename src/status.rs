@@ -0,0 +1,195 @@
+use crate::control;
+use crate::event::Event;
+use crate::get_char_titles;
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::{self, Receiver, Sender};
+
+const HISTORY_SIZE: usize = 256;
+/// The monitor repeats warns on every frame, a character counts as warning until they stop this long
+const WARN_HOLD: Duration = Duration::from_secs(5);
+
+static LAST_SEEN: RwLock<BTreeMap<String, LastSeen>> = RwLock::new(BTreeMap::new());
+static HISTORY: RwLock<VecDeque<RecordedEvent>> = RwLock::new(VecDeque::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+static RECORDED: LazyLock<Sender<RecordedEvent>> = LazyLock::new(|| broadcast::channel(64).0);
+
+/// Milliseconds since the unix epoch
+pub fn now_millis() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|duration| duration.as_millis() as u64)
+		.unwrap_or(0)
+}
+
+#[derive(Default, Clone, Copy)]
+struct LastSeen {
+	warn: Option<u64>,
+	reminder: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordedEvent {
+	pub id: u64,
+	pub at: u64,
+	#[serde(flatten)]
+	pub event: Event,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CharacterStatus {
+	pub title: String,
+	pub warning: bool,
+	pub last_warn: Option<u64>,
+	pub last_reminder: Option<u64>,
+	pub snoozed_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Status {
+	pub paused: bool,
+	pub characters: Vec<CharacterStatus>,
+}
+
+/// Keeps the live state and recent history, only the first of a run of warns is kept in history
+pub fn record(event: &Event) -> RecordedEvent {
+	let recorded = RecordedEvent {
+		id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+		at: now_millis(),
+		event: event.clone(),
+	};
+	let at = recorded.at;
+	let mut last_seen = LAST_SEEN.write().unwrap();
+	let is_new = match event {
+		Event::Warn { title } => {
+			let seen = last_seen.entry(title.clone()).or_default();
+			let is_new = seen
+				.warn
+				.is_none_or(|warn| at.saturating_sub(warn) > WARN_HOLD.as_millis() as u64);
+			seen.warn = Some(at);
+			is_new
+		}
+		Event::Reminder { title } => {
+			last_seen.entry(title.clone()).or_default().reminder = Some(at);
+			true
+		}
+		_ => true,
+	};
+	if is_new {
+		let mut history = HISTORY.write().unwrap();
+		if history.len() == HISTORY_SIZE {
+			history.pop_front();
+		}
+		history.push_back(recorded.clone());
+	}
+	recorded
+}
+
+/// Records every event passing through the center and republishes it with its id
+pub fn spawn_recorder(sender: &Sender<Event>) {
+	let mut receiver = sender.subscribe();
+	tokio::spawn(async move {
+		loop {
+			if let Ok(event) = receiver.recv().await {
+				let _ = RECORDED.send(record(&event));
+			}
+		}
+	});
+}
+
+/// Live events as they are recorded
+pub fn subscribe() -> Receiver<RecordedEvent> {
+	RECORDED.subscribe()
+}
+
+pub fn get_history() -> Vec<RecordedEvent> {
+	HISTORY.read().unwrap().iter().cloned().collect()
+}
+
+/// History recorded after `last_id`, an id from a previous run replays everything
+pub fn get_history_since(last_id: u64) -> Vec<RecordedEvent> {
+	let last_id = if last_id >= NEXT_ID.load(Ordering::Relaxed) {
+		0
+	} else {
+		last_id
+	};
+	HISTORY
+		.read()
+		.unwrap()
+		.iter()
+		.filter(|recorded| recorded.id > last_id)
+		.cloned()
+		.collect()
+}
+
+pub fn get_status() -> Status {
+	let now = now_millis();
+	let last_seen = LAST_SEEN.read().unwrap();
+	let characters = get_char_titles()
+		.into_iter()
+		.map(|title| {
+			let seen = last_seen.get(&title).copied().unwrap_or_default();
+			CharacterStatus {
+				warning: seen
+					.warn
+					.is_some_and(|warn| now.saturating_sub(warn) <= WARN_HOLD.as_millis() as u64),
+				last_warn: seen.warn,
+				last_reminder: seen.reminder,
+				snoozed_secs: control::get_snooze(&title).map_or(0, |d| d.as_secs()),
+				title,
+			}
+		})
+		.collect();
+	Status {
+		paused: control::is_paused(),
+		characters,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::event::Event;
+	use crate::status::{get_history, get_history_since, record};
+
+	#[test]
+	fn warn_runs() {
+		let warn = Event::Warn {
+			title: "EVE - STATUS".to_string(),
+		};
+		record(&warn);
+		record(&warn);
+		record(&Event::Reminder {
+			title: "EVE - STATUS".to_string(),
+		});
+		let recorded = get_history()
+			.into_iter()
+			.filter(|recorded| match &recorded.event {
+				Event::Warn { title } | Event::Reminder { title } => title == "EVE - STATUS",
+				_ => false,
+			})
+			.count();
+		assert_eq!(recorded, 2);
+	}
+
+	#[test]
+	fn since() {
+		let first = record(&Event::Reminder {
+			title: "EVE - SINCE".to_string(),
+		});
+		let second = record(&Event::Reminder {
+			title: "EVE - SINCE".to_string(),
+		});
+		assert!(second.id > first.id);
+		let since = get_history_since(first.id);
+		assert!(since.iter().all(|recorded| recorded.id > first.id));
+		assert!(since.iter().any(|recorded| recorded.id == second.id));
+		assert!(
+			get_history_since(u64::MAX)
+				.iter()
+				.any(|recorded| recorded.id == first.id)
+		);
+	}
+}
@@ -0,0 +1,229 @@
+use crate::config::{ChatId, TelegramConfig};
+use crate::delivery::{Cooldown, send_with_retry};
+use crate::event::{Event, EventConsumer};
+use crate::tts::render_template;
+use anyhow::anyhow;
+use reqwest::Client;
+use reqwest::multipart::{Form, Part};
+use serde_json::json;
+use tokio::sync::broadcast::Sender;
+use tracing::warn;
+
+fn escape_html(text: &str) -> String {
+	text
+		.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+}
+
+/// Html text of a warn, its snapshot or a reminder, other events are not sent
+fn message_text(event: &Event) -> Option<String> {
+	let (icon, kind, title) = match event {
+		Event::Warn { title } | Event::Snapshot { title, .. } => ("🔴", "Warn", title),
+		Event::Reminder { title } => ("🟡", "Reminder", title),
+		Event::Snooze { .. } | Event::Pause { .. } => return None,
+	};
+	Some(format!(
+		"{icon} <b>{kind}</b> {}\n<i>{}</i>",
+		escape_html(&render_template("{character}", title)),
+		escape_html(title)
+	))
+}
+
+struct Telegram {
+	client: Client,
+	config: TelegramConfig,
+}
+
+impl Telegram {
+	fn new(config: TelegramConfig) -> anyhow::Result<Self> {
+		let client = Client::builder().timeout(config.timeout).build()?;
+		Ok(Self { client, config })
+	}
+
+	/// Joined by hand, `Url::join` reads the `bot123:` of the token as a scheme
+	fn method_url(&self, method: &str) -> anyhow::Result<reqwest::Url> {
+		let api_url = self.config.api_url.as_str().trim_end_matches('/');
+		Ok(reqwest::Url::parse(&format!(
+			"{api_url}/bot{}/{method}",
+			self.config.token
+		))?)
+	}
+
+	async fn send(&self, event: &Event) -> anyhow::Result<()> {
+		let Some(text) = message_text(event) else {
+			return Ok(());
+		};
+		let photo = match event {
+			// the warn itself was sent already, a snapshot without its photo would repeat it
+			Event::Snapshot { snapshot, .. } => {
+				if !self.config.attach_snapshot {
+					return Ok(());
+				}
+				match tokio::fs::read(&snapshot.path).await {
+					Ok(png) => Some(png),
+					Err(e) => {
+						warn!("read snapshot {:?} failed: {e}", snapshot.path);
+						return Ok(());
+					}
+				}
+			}
+			_ => None,
+		};
+		// the photo follows a warn that notified already
+		let silent = photo.is_some() || (event.is_reminder() && self.config.silent_reminders);
+		let url = self.method_url(if photo.is_some() {
+			"sendPhoto"
+		} else {
+			"sendMessage"
+		})?;
+
+		for chat_id in &self.config.chat_ids {
+			let chat = match chat_id {
+				ChatId::Id(id) => id.to_string(),
+				ChatId::Username(username) => username.clone(),
+			};
+			let result = send_with_retry(
+				|| {
+					let request = self.client.post(url.clone());
+					match &photo {
+						Some(png) => request.multipart(
+							Form::new()
+								.text("chat_id", chat.clone())
+								.text("caption", text.clone())
+								.text("parse_mode", "HTML")
+								.text("disable_notification", silent.to_string())
+								.part("photo", Part::bytes(png.clone()).file_name("snapshot.png")),
+						),
+						None => request.json(&json!({
+							"chat_id": chat_id,
+							"text": text,
+							"parse_mode": "HTML",
+							"disable_notification": silent,
+						})),
+					}
+				},
+				self.config.retries,
+				self.config.retry_spacing,
+				self.config.max_retry_spacing,
+			)
+			.await;
+			if let Err(e) = result {
+				warn!("telegram delivery to {chat} failed: {e}");
+			}
+		}
+		Ok(())
+	}
+}
+
+pub struct TelegramController {
+	sender: Option<Sender<Event>>,
+	config: TelegramConfig,
+}
+
+impl TelegramController {
+	pub fn new(config: TelegramConfig) -> Self {
+		Self {
+			sender: None,
+			config,
+		}
+	}
+}
+
+impl EventConsumer for TelegramController {
+	fn inject(&mut self, sender: Sender<Event>) {
+		self.sender = Some(sender)
+	}
+
+	fn start(&self) -> anyhow::Result<()> {
+		if self.sender.is_none() {
+			return Err(anyhow!("There is no sender"));
+		}
+		if self.config.chat_ids.is_empty() {
+			return Err(anyhow!("There is no telegram chat id"));
+		}
+		let mut receiver = self.sender.clone().unwrap().subscribe();
+		let telegram = Telegram::new(self.config.clone())?;
+		let mut cooldown = Cooldown::new(self.config.cooldown);
+
+		tokio::spawn(async move {
+			loop {
+				if let Ok(event) = receiver.recv().await {
+					if !cooldown.allow(&event) {
+						continue;
+					}
+					if let Err(e) = telegram.send(&event).await {
+						warn!("telegram delivery failed: {e}");
+					}
+				}
+			}
+		});
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::config::{ChatId, TelegramConfig};
+	use crate::delivery::tests::record_server;
+	use crate::event::Event;
+	use crate::snapshot::Snapshot;
+	use crate::telegram::Telegram;
+	use axum::http::StatusCode;
+	use axum::response::IntoResponse;
+	use std::time::Duration;
+	use tokio::test;
+
+	#[test]
+	async fn messages_and_photos() {
+		let (addr, mut records) = record_server(0, || StatusCode::OK.into_response()).await;
+
+		let telegram = Telegram::new(TelegramConfig {
+			token: "123:secret".to_string(),
+			chat_ids: vec![ChatId::Id(42), ChatId::Username("@intel".to_string())],
+			api_url: format!("http://{addr}").parse().unwrap(),
+			attach_snapshot: true,
+			silent_reminders: true,
+			timeout: Duration::from_secs(5),
+			retries: 0,
+			retry_spacing: Duration::ZERO,
+			max_retry_spacing: Duration::from_secs(1),
+			cooldown: Duration::ZERO,
+		})
+		.unwrap();
+
+		let reminder = Event::Reminder {
+			title: "EVE - <CHAR1>".to_string(),
+		};
+		telegram.send(&reminder).await.unwrap();
+		let recorded = records.recv().await.unwrap();
+		assert_eq!(recorded.path, "/bot123:secret/sendMessage");
+		assert!(recorded.body.contains(r#""chat_id":42"#));
+		assert!(recorded.body.contains(r#""disable_notification":true"#));
+		assert!(recorded.body.contains("&lt;CHAR1&gt;"));
+		let recorded = records.recv().await.unwrap();
+		assert!(recorded.body.contains(r#""chat_id":"@intel""#));
+
+		let snapshot_path = std::env::temp_dir().join(format!("telegram-{}.png", std::process::id()));
+		tokio::fs::write(&snapshot_path, b"png").await.unwrap();
+		let snapshot = Event::Snapshot {
+			title: "EVE - CHAR1".to_string(),
+			snapshot: Snapshot {
+				path: snapshot_path.clone(),
+				name: "2026-01-01/00-00-00.000-CHAR1.png".to_string(),
+				thumbnail: None,
+			},
+		};
+		telegram.send(&snapshot).await.unwrap();
+		let recorded = records.recv().await.unwrap();
+		assert_eq!(recorded.path, "/bot123:secret/sendPhoto");
+		assert!(recorded.body.contains("snapshot.png"));
+		assert!(
+			recorded
+				.body
+				.contains("\"disable_notification\"\r\n\r\ntrue")
+		);
+		tokio::fs::remove_file(snapshot_path).await.unwrap();
+	}
+}
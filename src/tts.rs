@@ -0,0 +1,183 @@
+use crate::config::TtsBackend;
+use anyhow::anyhow;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const CACHE_EXTENSIONS: [&str; 4] = ["wav", "mp3", "ogg", "flac"];
+/// How often a running engine is checked for its exit
+const COMMAND_POLL: Duration = Duration::from_millis(10);
+
+pub trait Synthesizer: Send + Sync {
+	/// Returns encoded audio (wav/mp3/...) that rodio can decode
+	fn synthesize(&self, text: &str) -> anyhow::Result<Arc<Vec<u8>>>;
+}
+
+impl TtsBackend {
+	pub fn to_synthesizer(&self) -> Arc<dyn Synthesizer> {
+		match self {
+			Self::Command {
+				program,
+				args,
+				timeout,
+			} => Arc::new(CommandSynthesizer::new(program, args, *timeout)),
+			Self::Cache { dir } => Arc::new(CacheSynthesizer::new(dir)),
+		}
+	}
+}
+
+pub fn render_template(template: &str, title: &str) -> String {
+	let character = title.strip_prefix("EVE - ").unwrap_or(title);
+	template
+		.replace("{character}", character)
+		.replace("{title}", title)
+}
+
+pub fn file_stem(text: &str) -> String {
+	text
+		.chars()
+		.map(|c| if c.is_alphanumeric() { c } else { '_' })
+		.collect()
+}
+
+/// Runs a local offline engine (espeak, piper, say...) that writes audio to `{output}`
+pub struct CommandSynthesizer {
+	program: PathBuf,
+	args: Vec<String>,
+	timeout: Duration,
+	cache: Mutex<HashMap<String, Arc<Vec<u8>>>>,
+}
+
+impl CommandSynthesizer {
+	pub fn new(program: &Path, args: &[String], timeout: Duration) -> Self {
+		Self {
+			program: program.to_path_buf(),
+			args: args.to_vec(),
+			timeout,
+			cache: Mutex::new(HashMap::new()),
+		}
+	}
+}
+
+/// Removed when dropped, whether the engine succeeded or not
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+	fn drop(&mut self) {
+		let _ = std::fs::remove_file(&self.0);
+	}
+}
+
+impl Synthesizer for CommandSynthesizer {
+	fn synthesize(&self, text: &str) -> anyhow::Result<Arc<Vec<u8>>> {
+		if let Some(audio) = self.cache.lock().unwrap().get(text) {
+			return Ok(Arc::clone(audio));
+		}
+		let output =
+			TempFile(std::env::temp_dir().join(format!("reporting-tts-{}.wav", file_stem(text))));
+		let output_str = output.0.to_string_lossy();
+		let args = self
+			.args
+			.iter()
+			.map(|arg| arg.replace("{text}", text).replace("{output}", &output_str));
+		let mut child = Command::new(&self.program).args(args).spawn()?;
+		let deadline = Instant::now() + self.timeout;
+		let status = loop {
+			if let Some(status) = child.try_wait()? {
+				break status;
+			}
+			if Instant::now() >= deadline {
+				let _ = child.kill();
+				let _ = child.wait();
+				return Err(anyhow!("tts command timed out after {:?}", self.timeout));
+			}
+			std::thread::sleep(COMMAND_POLL);
+		};
+		if !status.success() {
+			return Err(anyhow!("tts command exited with {status}"));
+		}
+		let audio = Arc::new(std::fs::read(&output.0)?);
+		self
+			.cache
+			.lock()
+			.unwrap()
+			.insert(text.to_string(), Arc::clone(&audio));
+		Ok(audio)
+	}
+}
+
+/// Reads pre-rendered announcements from `dir`, named after the spoken text
+pub struct CacheSynthesizer {
+	dir: PathBuf,
+}
+
+impl CacheSynthesizer {
+	pub fn new(dir: &Path) -> Self {
+		Self {
+			dir: dir.to_path_buf(),
+		}
+	}
+}
+
+impl Synthesizer for CacheSynthesizer {
+	fn synthesize(&self, text: &str) -> anyhow::Result<Arc<Vec<u8>>> {
+		let stem = file_stem(text);
+		CACHE_EXTENSIONS
+			.iter()
+			.map(|ext| self.dir.join(format!("{stem}.{ext}")))
+			.find(|path| path.exists())
+			.ok_or_else(|| anyhow!("no pre-rendered voice for \"{text}\" in {:?}", self.dir))
+			.and_then(|path| Ok(Arc::new(std::fs::read(path)?)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::tts::{CommandSynthesizer, Synthesizer, file_stem, render_template};
+	use std::time::{Duration, Instant};
+
+	#[test]
+	fn template() {
+		assert_eq!(
+			render_template("{character} hostile in local", "EVE - CHAR1"),
+			"CHAR1 hostile in local"
+		);
+		assert_eq!(render_template("{title}", "EVE - CHAR1"), "EVE - CHAR1");
+		assert_eq!(file_stem("CHAR1 hostile"), "CHAR1_hostile");
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn command_timeout_and_cleanup() {
+		let output = std::env::temp_dir().join("reporting-tts-CHAR1_hostile.wav");
+		let write = |script: &str, timeout| {
+			CommandSynthesizer::new(
+				"sh".as_ref(),
+				&["-c".to_string(), script.to_string(), "{output}".to_string()],
+				timeout,
+			)
+		};
+		let synthesizer = write(r#"printf audio > "$0""#, Duration::from_secs(5));
+		assert_eq!(
+			&synthesizer.synthesize("CHAR1 hostile").unwrap()[..],
+			b"audio"
+		);
+		assert!(!output.exists());
+
+		let synthesizer = write(r#"printf audio > "$0"; exit 1"#, Duration::from_secs(5));
+		assert!(synthesizer.synthesize("CHAR1 hostile").is_err());
+		assert!(!output.exists());
+
+		let started = Instant::now();
+		let synthesizer = write(
+			r#"printf audio > "$0"; sleep 10"#,
+			Duration::from_millis(200),
+		);
+		let error = synthesizer.synthesize("CHAR1 hostile").unwrap_err();
+		assert!(error.to_string().contains("timed out"));
+		assert!(started.elapsed() < Duration::from_secs(5));
+		assert!(!output.exists());
+	}
+}
@@ -1,86 +1,436 @@
+use crate::config::VoiceConfig;
 use crate::event::{Event, EventConsumer};
+use crate::tts::{Synthesizer, render_template};
 use anyhow::anyhow;
-use rodio::{OutputStream, Sink};
-use std::fs::File;
-use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
-use tokio::fs::File as AFile;
+use rodio::buffer::SamplesBuffer;
+use rodio::cpal::traits::HostTrait;
+use rodio::{DeviceTrait, OutputStream, OutputStreamBuilder, Sink, Source};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::Notify;
 use tokio::sync::broadcast::Sender;
+use tokio::time::Instant;
+use tracing::{info, warn};
 
-#[derive(Clone)]
-pub struct VoicePlayer {
-	warn_voice_path: PathBuf,
-	reminder_voice_path: PathBuf,
-	warn_playing: Arc<AtomicBool>,
-	reminder_playing: Arc<AtomicBool>,
-	stream_handle: Arc<OutputStream>,
+const DEFAULT_WARN_VOICE: &[u8] = include_bytes!("../assets/warn.wav");
+const DEFAULT_REMINDER_VOICE: &[u8] = include_bytes!("../assets/reminder.wav");
+/// How often a playing sound is checked for its end, sinks can't tell when they finish
+const SINK_POLL: Duration = Duration::from_millis(50);
+
+fn decode(data: Vec<u8>) -> anyhow::Result<SamplesBuffer> {
+	let decoder = rodio::Decoder::new(Cursor::new(data))?;
+	let channels = decoder.channels();
+	let sample_rate = decoder.sample_rate();
+	let samples = decoder.collect::<Vec<_>>();
+	if samples.is_empty() {
+		return Err(anyhow!("no audio samples"));
+	}
+	Ok(SamplesBuffer::new(channels, sample_rate, samples))
 }
 
-impl VoicePlayer {
-	pub async fn new(warn_voice_path: &Path, reminder_voice_path: &Path) -> anyhow::Result<Self> {
-		let warn_playing = Arc::new(AtomicBool::new(false));
-		let reminder_playing = Arc::new(AtomicBool::new(false));
-		let stream_handle = Arc::new(rodio::OutputStreamBuilder::open_default_stream()?);
+fn load_sound(path: &Path) -> anyhow::Result<SamplesBuffer> {
+	std::fs::read(path)
+		.map_err(anyhow::Error::from)
+		.and_then(decode)
+		.map_err(|e| anyhow!("cannot load voice {path:?}: {e}"))
+}
+
+/// Every sound decoded once at startup, so a broken file is reported before the first alert
+struct Sounds {
+	warn: SamplesBuffer,
+	reminder: SamplesBuffer,
+	characters: HashMap<String, (Option<SamplesBuffer>, Option<SamplesBuffer>)>,
+}
+
+impl Sounds {
+	fn load(config: &VoiceConfig) -> anyhow::Result<Self> {
+		let warn = match &config.warn_voice_path {
+			Some(path) => load_sound(path)?,
+			None => decode(DEFAULT_WARN_VOICE.to_vec())?,
+		};
+		let reminder = match &config.reminder_voice_path {
+			Some(path) => load_sound(path)?,
+			None => decode(DEFAULT_REMINDER_VOICE.to_vec())?,
+		};
+		let characters = config
+			.characters
+			.iter()
+			.map(|(title, voice)| {
+				let warn = voice
+					.warn_voice_path
+					.as_deref()
+					.map(load_sound)
+					.transpose()?;
+				let reminder = voice
+					.reminder_voice_path
+					.as_deref()
+					.map(load_sound)
+					.transpose()?;
+				Ok((title.clone(), (warn, reminder)))
+			})
+			.collect::<anyhow::Result<_>>()?;
 		Ok(Self {
-			warn_voice_path: warn_voice_path.to_path_buf(),
-			reminder_voice_path: reminder_voice_path.to_path_buf(),
-			warn_playing,
-			reminder_playing,
-			stream_handle,
+			warn,
+			reminder,
+			characters,
 		})
 	}
 
-	pub async fn play_warn(&self) -> anyhow::Result<()> {
-		let file = AFile::open(&self.warn_voice_path).await?.into_std().await;
-		let playing = Arc::clone(&self.warn_playing);
-		self.play_voice(file, playing);
-		Ok(())
+	fn get(&self, title: &str, kind: VoiceKind) -> SamplesBuffer {
+		let character = self.characters.get(title);
+		match kind {
+			VoiceKind::Warn => character.and_then(|(warn, _)| warn.clone()),
+			VoiceKind::Reminder => character.and_then(|(_, reminder)| reminder.clone()),
+		}
+		.unwrap_or_else(|| match kind {
+			VoiceKind::Warn => self.warn.clone(),
+			VoiceKind::Reminder => self.reminder.clone(),
+		})
 	}
+}
 
-	pub async fn play_reminder(&self) -> anyhow::Result<()> {
-		let file = AFile::open(&self.reminder_voice_path)
-			.await?
-			.into_std()
-			.await;
-		let playing = Arc::clone(&self.reminder_playing);
-		self.play_voice(file, playing);
-		Ok(())
+/// Ordered by priority, a higher kind preempts a lower one
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum VoiceKind {
+	Reminder,
+	Warn,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Interrupt {
+	Preempt,
+	Acknowledge,
+}
+
+#[derive(Clone)]
+struct Playback {
+	title: String,
+	kind: VoiceKind,
+	sound: SamplesBuffer,
+	volume: f32,
+	announcement: Option<(String, Arc<dyn Synthesizer>)>,
+}
+
+impl Playback {
+	fn is(&self, title: &str, kind: VoiceKind) -> bool {
+		self.title == title && self.kind == kind
+	}
+
+	fn append_to(&self, sink: &Sink) {
+		sink.set_volume(self.volume);
+		sink.append(self.sound.clone());
+		if let Some((text, synthesizer)) = &self.announcement {
+			match synthesizer
+				.synthesize(text)
+				.and_then(|audio| decode(audio.to_vec()))
+			{
+				Ok(speech) => sink.append(speech),
+				Err(e) => warn!("synthesize \"{text}\" failed: {e}"),
+			}
+		}
+	}
+}
+
+#[derive(Default)]
+struct QueueState {
+	queue: Vec<Playback>,
+	repeating: Vec<(Instant, Playback)>,
+	current: Option<(String, VoiceKind)>,
+	interrupt: Option<Interrupt>,
+	/// Set once the player is dropped, the worker stops
+	closed: bool,
+}
+
+impl QueueState {
+	fn contains(&self, title: &str, kind: VoiceKind) -> bool {
+		self
+			.current
+			.as_ref()
+			.is_some_and(|(t, k)| t == title && *k == kind)
+			|| self.queue.iter().any(|p| p.is(title, kind))
+			|| self.repeating.iter().any(|(_, p)| p.is(title, kind))
+	}
+
+	fn take_due_repeats(&mut self) {
+		let now = Instant::now();
+		let (due, pending) = std::mem::take(&mut self.repeating)
+			.into_iter()
+			.partition::<Vec<_>, _>(|(at, _)| *at <= now);
+		self.repeating = pending;
+		self
+			.queue
+			.extend(due.into_iter().map(|(_, playback)| playback));
+	}
+
+	fn pop(&mut self) -> Option<Playback> {
+		let index = self
+			.queue
+			.iter()
+			.enumerate()
+			.rev()
+			.max_by_key(|(_, playback)| playback.kind)
+			.map(|(index, _)| index)?;
+		Some(self.queue.remove(index))
+	}
+}
+
+/// A sound handed to the output device
+trait AudioSink: Send + Sync {
+	fn empty(&self) -> bool;
+	fn stop(&self);
+}
+
+impl AudioSink for Sink {
+	fn empty(&self) -> bool {
+		Sink::empty(self)
+	}
+
+	fn stop(&self) {
+		Sink::stop(self)
+	}
+}
+
+trait AudioOutput: Send + 'static {
+	fn play(&self, playback: &Playback) -> Box<dyn AudioSink>;
+}
+
+impl AudioOutput for OutputStream {
+	fn play(&self, playback: &Playback) -> Box<dyn AudioSink> {
+		let sink = Sink::connect_new(self.mixer());
+		playback.append_to(&sink);
+		Box::new(sink)
 	}
+}
 
-	fn play_voice(&self, file: File, playing: Arc<AtomicBool>) {
-		if playing.load(Ordering::Relaxed) {
+/// Plays one sound at a time, highest priority first
+#[derive(Default)]
+struct PlaybackQueue {
+	state: Mutex<QueueState>,
+	/// Wakes the worker, a single waiter so a notify before the wait is kept
+	changed: Notify,
+}
+
+impl PlaybackQueue {
+	fn lock(&self) -> MutexGuard<'_, QueueState> {
+		self.state.lock().unwrap()
+	}
+
+	fn push(&self, playback: Playback) {
+		let mut state = self.lock();
+		if state.contains(&playback.title, playback.kind) {
 			return;
 		}
-		let stream_handle = Arc::clone(&self.stream_handle);
-		playing.fetch_not(Ordering::Relaxed);
-		tokio::task::spawn_blocking(move || {
-			let task = || {
-				let decoder = rodio::Decoder::new(file)?;
-				let sink = Sink::connect_new(stream_handle.mixer());
-				sink.append(decoder);
-				sink.sleep_until_end();
-				playing.fetch_not(Ordering::Relaxed);
-				Ok::<(), anyhow::Error>(())
+		if state
+			.current
+			.as_ref()
+			.is_some_and(|(_, kind)| *kind < playback.kind)
+		{
+			state.interrupt = Some(Interrupt::Preempt);
+		}
+		state.queue.push(playback);
+		self.changed.notify_one();
+	}
+
+	fn acknowledge(&self, title: &str) {
+		let mut state = self.lock();
+		state.queue.retain(|playback| playback.title != title);
+		state
+			.repeating
+			.retain(|(_, playback)| playback.title != title);
+		if state.current.as_ref().is_some_and(|(t, _)| t == title) {
+			state.interrupt = Some(Interrupt::Acknowledge);
+		}
+		self.changed.notify_one();
+	}
+
+	fn close(&self) {
+		self.lock().closed = true;
+		self.changed.notify_one();
+	}
+
+	/// Waits for the next sound to play, `None` once the queue is closed
+	async fn next(&self) -> Option<Playback> {
+		loop {
+			let next_repeat = {
+				let mut state = self.lock();
+				if state.closed {
+					return None;
+				}
+				state.take_due_repeats();
+				if let Some(playback) = state.pop() {
+					state.current = Some((playback.title.clone(), playback.kind));
+					return Some(playback);
+				}
+				state.repeating.iter().map(|(at, _)| *at).min()
 			};
-			if task().is_err() {
-				playing.fetch_not(Ordering::Relaxed);
+			match next_repeat {
+				Some(at) => {
+					let _ = tokio::time::timeout_at(at, self.changed.notified()).await;
+				}
+				None => self.changed.notified().await,
+			}
+		}
+	}
+
+	async fn wait_until_end(&self, sink: &dyn AudioSink) {
+		loop {
+			{
+				let state = self.lock();
+				if state.interrupt.is_some() || state.closed || sink.empty() {
+					return;
+				}
+			}
+			let _ = tokio::time::timeout(SINK_POLL, self.changed.notified()).await;
+		}
+	}
+
+	fn finish(&self, playback: Playback, repeat_spacing: Option<Duration>) {
+		let mut state = self.lock();
+		state.current = None;
+		match state.interrupt.take() {
+			Some(Interrupt::Preempt) => state.queue.insert(0, playback),
+			Some(Interrupt::Acknowledge) => {}
+			None => {
+				if let Some(spacing) = repeat_spacing {
+					state.repeating.push((Instant::now() + spacing, playback));
+				}
+			}
+		}
+	}
+}
+
+fn open_stream(device: Option<&str>) -> anyhow::Result<OutputStream> {
+	let Some(name) = device else {
+		return Ok(OutputStreamBuilder::open_default_stream()?);
+	};
+	let device = rodio::cpal::default_host()
+		.output_devices()?
+		.find(|device| device.name().is_ok_and(|n| n == name))
+		.ok_or_else(|| anyhow!("output device \"{name}\" not found"))?;
+	Ok(OutputStreamBuilder::from_device(device)?.open_stream()?)
+}
+
+/// Closes the queue when the last clone of the player is dropped, which ends the worker
+struct QueueCloser(Arc<PlaybackQueue>);
+
+impl Drop for QueueCloser {
+	fn drop(&mut self) {
+		self.0.close();
+	}
+}
+
+#[derive(Clone)]
+pub struct VoicePlayer {
+	config: VoiceConfig,
+	sounds: Arc<Sounds>,
+	synthesizer: Option<Arc<dyn Synthesizer>>,
+	queue: Arc<PlaybackQueue>,
+	_closer: Arc<QueueCloser>,
+}
+
+impl VoicePlayer {
+	pub fn new(config: VoiceConfig) -> anyhow::Result<Self> {
+		let sounds = Sounds::load(&config)?;
+		let stream_handle = open_stream(config.output_device.as_deref())?;
+		Ok(Self::with_output(config, sounds, stream_handle))
+	}
+
+	fn with_output(config: VoiceConfig, sounds: Sounds, output: impl AudioOutput) -> Self {
+		let sounds = Arc::new(sounds);
+		let synthesizer = config.tts.as_ref().map(|tts| tts.backend.to_synthesizer());
+		let queue = Arc::new(PlaybackQueue::default());
+		let repeat_spacing = config
+			.repeat_until_acknowledged
+			.then_some(config.repeat_spacing);
+
+		let worker_queue = Arc::clone(&queue);
+		let output = Arc::new(Mutex::new(output));
+		tokio::spawn(async move {
+			while let Some(playback) = worker_queue.next().await {
+				let output = Arc::clone(&output);
+				let played = playback.clone();
+				// synthesizing the announcement blocks
+				let sink = tokio::task::spawn_blocking(move || output.lock().unwrap().play(&played)).await;
+				match sink {
+					Ok(sink) => {
+						worker_queue.wait_until_end(sink.as_ref()).await;
+						sink.stop();
+					}
+					Err(e) => warn!("play voice of {} failed: {e}", playback.title),
+				}
+				worker_queue.finish(playback, repeat_spacing);
 			}
 		});
+
+		Self {
+			config,
+			sounds,
+			synthesizer,
+			_closer: Arc::new(QueueCloser(Arc::clone(&queue))),
+			queue,
+		}
+	}
+
+	pub fn play_warn(&self, title: &str) {
+		self.queue.push(self.playback(title, VoiceKind::Warn));
+	}
+
+	pub fn play_reminder(&self, title: &str) {
+		self.queue.push(self.playback(title, VoiceKind::Reminder));
+	}
+
+	/// Stops the current alert of a character and cancels its repeats
+	pub fn acknowledge(&self, title: &str) {
+		info!("voice acknowledged for {title}");
+		self.queue.acknowledge(title);
+	}
+
+	fn playback(&self, title: &str, kind: VoiceKind) -> Playback {
+		let voice = self
+			.config
+			.characters
+			.get(title)
+			.cloned()
+			.unwrap_or_default();
+		let volume = match kind {
+			VoiceKind::Warn => voice.warn_volume.unwrap_or(self.config.warn_volume),
+			VoiceKind::Reminder => voice.reminder_volume.unwrap_or(self.config.reminder_volume),
+		};
+		let announcement =
+			self
+				.config
+				.tts
+				.as_ref()
+				.zip(self.synthesizer.as_ref())
+				.map(|(tts, synthesizer)| {
+					let template = match kind {
+						VoiceKind::Warn => &tts.warn_template,
+						VoiceKind::Reminder => &tts.reminder_template,
+					};
+					(render_template(template, title), Arc::clone(synthesizer))
+				});
+		Playback {
+			title: title.to_string(),
+			kind,
+			sound: self.sounds.get(title, kind),
+			volume,
+			announcement,
+		}
 	}
 }
 
 pub struct VoicePlayerController {
-	warn_voice_path: PathBuf,
-	reminder_voice_path: PathBuf,
+	config: VoiceConfig,
 	event_sender: Option<Sender<Event>>,
 }
 
 impl VoicePlayerController {
-	pub fn new(warn_voice_path: &Path, reminder_voice_path: &Path) -> Self {
+	pub fn new(config: VoiceConfig) -> Self {
 		Self {
-			warn_voice_path: warn_voice_path.to_path_buf(),
-			reminder_voice_path: reminder_voice_path.to_path_buf(),
+			config,
 			event_sender: None,
 		}
 	}
@@ -95,19 +445,17 @@ impl EventConsumer for VoicePlayerController {
 			return Err(anyhow!("There are no receiver"));
 		}
 		let mut receiver = self.event_sender.clone().unwrap().subscribe();
-		let warn_voice_path = self.warn_voice_path.clone();
-		let reminder_voice_path = self.reminder_voice_path.clone();
+		let voice_player = VoicePlayer::new(self.config.clone())?;
 
 		tokio::spawn(async move {
-			let voice_player = VoicePlayer::new(&warn_voice_path, &reminder_voice_path)
-				.await
-				.unwrap();
 			loop {
 				if let Ok(event) = receiver.recv().await {
-					let _ = match event {
-						Event::Warn { title: _ } => voice_player.play_warn().await,
-						Event::Reminder { title: _ } => voice_player.play_reminder().await,
-					};
+					match event {
+						Event::Warn { title } => voice_player.play_warn(&title),
+						Event::Reminder { title } => voice_player.play_reminder(&title),
+						Event::Snooze { title, secs } if secs > 0 => voice_player.acknowledge(&title),
+						Event::Snapshot { .. } | Event::Snooze { .. } | Event::Pause { .. } => {}
+					}
 				}
 			}
 		});
@@ -118,20 +466,220 @@ impl EventConsumer for VoicePlayerController {
 
 #[cfg(test)]
 mod tests {
-	use crate::voice_player::VoicePlayer;
-	use std::path::PathBuf;
+	use crate::config::VoiceConfig;
+	use crate::voice_player::{
+		AudioOutput, AudioSink, DEFAULT_REMINDER_VOICE, DEFAULT_WARN_VOICE, Playback, PlaybackQueue,
+		Sounds, VoiceKind, VoicePlayer, decode,
+	};
+	use rodio::buffer::SamplesBuffer;
+	use std::sync::Arc;
+	use std::sync::atomic::{AtomicBool, Ordering};
 	use std::time::Duration;
+	use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
 	use tokio::test;
+	use tokio::time::Instant;
+
+	struct Record {
+		title: String,
+		kind: VoiceKind,
+		volume: f32,
+		at: Instant,
+		stopped: Arc<AtomicBool>,
+	}
+
+	struct RecordingSink {
+		until: Instant,
+		stopped: Arc<AtomicBool>,
+	}
+
+	impl AudioSink for RecordingSink {
+		fn empty(&self) -> bool {
+			self.stopped.load(Ordering::Relaxed) || Instant::now() >= self.until
+		}
+
+		fn stop(&self) {
+			if Instant::now() < self.until {
+				self.stopped.store(true, Ordering::Relaxed);
+			}
+		}
+	}
+
+	/// Pretends every sound lasts `length` and reports what was played
+	struct RecordingOutput {
+		length: Duration,
+		records: UnboundedSender<Record>,
+	}
+
+	impl AudioOutput for RecordingOutput {
+		fn play(&self, playback: &Playback) -> Box<dyn AudioSink> {
+			let stopped = Arc::new(AtomicBool::new(false));
+			let _ = self.records.send(Record {
+				title: playback.title.clone(),
+				kind: playback.kind,
+				volume: playback.volume,
+				at: Instant::now(),
+				stopped: Arc::clone(&stopped),
+			});
+			Box::new(RecordingSink {
+				until: Instant::now() + self.length,
+				stopped,
+			})
+		}
+	}
+
+	fn recording_player(config: &str, length: Duration) -> (VoicePlayer, UnboundedReceiver<Record>) {
+		let config = toml::from_str::<VoiceConfig>(config).unwrap();
+		let sounds = Sounds::load(&config).unwrap();
+		let (records, receiver) = unbounded_channel();
+		let output = RecordingOutput { length, records };
+		(VoicePlayer::with_output(config, sounds, output), receiver)
+	}
+
+	/// The time is paused in these tests, so waiting for a record that never comes returns at once
+	async fn next_record(receiver: &mut UnboundedReceiver<Record>) -> Option<Record> {
+		tokio::time::timeout(Duration::from_secs(60), receiver.recv())
+			.await
+			.ok()
+			.flatten()
+	}
+
+	/// Title, kind and start in milliseconds since `start` of a record
+	fn played(record: &Record, start: Instant) -> (&str, VoiceKind, u128) {
+		(
+			record.title.as_str(),
+			record.kind,
+			(record.at - start).as_millis(),
+		)
+	}
+
+	fn playback(title: &str, kind: VoiceKind) -> Playback {
+		Playback {
+			title: title.to_string(),
+			kind,
+			sound: SamplesBuffer::new(1, 1, vec![0.0]),
+			volume: 1.0,
+			announcement: None,
+		}
+	}
+
+	#[test]
+	async fn queue_priority() {
+		let queue = PlaybackQueue::default();
+		queue.push(playback("EVE - CHAR1", VoiceKind::Reminder));
+		queue.push(playback("EVE - CHAR2", VoiceKind::Warn));
+		queue.push(playback("EVE - CHAR2", VoiceKind::Warn));
+		queue.push(playback("EVE - CHAR3", VoiceKind::Warn));
+		let first = queue.next().await.unwrap();
+		assert!(first.is("EVE - CHAR2", VoiceKind::Warn));
+		queue.finish(first, None);
+		assert!(
+			queue
+				.next()
+				.await
+				.unwrap()
+				.is("EVE - CHAR3", VoiceKind::Warn)
+		);
+		queue.acknowledge("EVE - CHAR3");
+		assert!(queue.lock().interrupt.is_some());
+		queue.close();
+		assert!(queue.next().await.is_none());
+	}
 
 	#[test]
-	async fn test() {
-		let warn = PathBuf::from("D:\\dev\\rust\\project\\eve\\reporting\\warn.mp3");
-		let reminder = PathBuf::from("D:\\dev\\rust\\project\\eve\\reporting\\reminder.mp3");
-		let vp = VoicePlayer::new(&warn, &reminder).await.unwrap();
-		vp.play_reminder().await.unwrap();
-		tokio::time::sleep(Duration::from_secs(1)).await;
-		vp.play_reminder().await.unwrap();
-		tokio::time::sleep(Duration::from_secs(3)).await;
-		vp.play_reminder().await.unwrap();
+	async fn sounds() {
+		assert!(decode(DEFAULT_WARN_VOICE.to_vec()).is_ok());
+		assert!(decode(DEFAULT_REMINDER_VOICE.to_vec()).is_ok());
+		assert!(decode(b"not audio".to_vec()).is_err());
+
+		let config = toml::from_str::<VoiceConfig>("").unwrap();
+		assert!(Sounds::load(&config).is_ok());
+		let config = toml::from_str::<VoiceConfig>(r#"warn_voice_path = "missing.mp3""#).unwrap();
+		assert!(Sounds::load(&config).is_err());
+	}
+
+	#[test(start_paused = true)]
+	async fn dedup() {
+		let start = Instant::now();
+		let (vp, mut records) = recording_player("", Duration::from_millis(300));
+		vp.play_reminder("EVE - CHAR1");
+		let first = next_record(&mut records).await.unwrap();
+		assert_eq!(
+			played(&first, start),
+			("EVE - CHAR1", VoiceKind::Reminder, 0)
+		);
+		vp.play_reminder("EVE - CHAR1");
+		vp.play_reminder("EVE - CHAR2");
+		let second = next_record(&mut records).await.unwrap();
+		assert_eq!(
+			played(&second, start),
+			("EVE - CHAR2", VoiceKind::Reminder, 300)
+		);
+		vp.play_reminder("EVE - CHAR1");
+		let third = next_record(&mut records).await.unwrap();
+		assert_eq!(
+			played(&third, start),
+			("EVE - CHAR1", VoiceKind::Reminder, 600)
+		);
+		assert!(next_record(&mut records).await.is_none());
+
+		// the worker ends with the player, dropping the output
+		drop(vp);
+		let closed = tokio::time::timeout(Duration::from_secs(60), records.recv()).await;
+		assert!(matches!(closed, Ok(None)));
+	}
+
+	#[test(start_paused = true)]
+	async fn warn_preempts_reminder() {
+		let start = Instant::now();
+		let (vp, mut records) = recording_player(
+			r#"
+			reminder_volume = 0.5
+			characters."EVE - CHAR2".warn_volume = 0.8
+		"#,
+			Duration::from_secs(1),
+		);
+		vp.play_reminder("EVE - CHAR1");
+		let reminder = next_record(&mut records).await.unwrap();
+		assert_eq!(reminder.volume, 0.5);
+		vp.play_warn("EVE - CHAR2");
+		let warn = next_record(&mut records).await.unwrap();
+		assert_eq!(warn.volume, 0.8);
+		let replay = next_record(&mut records).await.unwrap();
+		assert_eq!(
+			[&reminder, &warn, &replay].map(|record| played(record, start)),
+			[
+				("EVE - CHAR1", VoiceKind::Reminder, 0),
+				("EVE - CHAR2", VoiceKind::Warn, 0),
+				("EVE - CHAR1", VoiceKind::Reminder, 1000),
+			]
+		);
+		assert!(reminder.stopped.load(Ordering::Relaxed));
+		assert!(!warn.stopped.load(Ordering::Relaxed));
+		assert!(next_record(&mut records).await.is_none());
+	}
+
+	#[test(start_paused = true)]
+	async fn repeat_until_acknowledged() {
+		let start = Instant::now();
+		let (vp, mut records) = recording_player(
+			r#"
+			repeat_until_acknowledged = true
+			repeat_spacing = { secs = 0, nanos = 100000000 }
+		"#,
+			Duration::from_millis(100),
+		);
+		vp.play_warn("EVE - CHAR1");
+		let first = next_record(&mut records).await.unwrap();
+		let second = next_record(&mut records).await.unwrap();
+		assert_eq!(
+			[&first, &second].map(|record| played(record, start)),
+			[
+				("EVE - CHAR1", VoiceKind::Warn, 0),
+				("EVE - CHAR1", VoiceKind::Warn, 200),
+			]
+		);
+		vp.acknowledge("EVE - CHAR1");
+		assert!(next_record(&mut records).await.is_none());
+		assert!(second.stopped.load(Ordering::Relaxed));
 	}
 }
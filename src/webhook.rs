@@ -0,0 +1,176 @@
+use crate::config::WebhookConfig;
+use crate::delivery::{Cooldown, header_map, send_with_retry};
+use crate::event::{Event, EventConsumer};
+use crate::tts::render_template;
+use anyhow::anyhow;
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use reqwest::header::{CONTENT_TYPE, HeaderMap, HeaderValue};
+use sha2::Sha256;
+use tokio::sync::broadcast::Sender;
+use tracing::{debug, warn};
+
+pub fn render_body(template: Option<&str>, event: &Event) -> anyhow::Result<String> {
+	let json = serde_json::to_string(event)?;
+	let Some(template) = template else {
+		return Ok(json);
+	};
+	let kind: &'static str = event.into();
+	Ok(
+		render_template(template, event.title().unwrap_or_default())
+			.replace("{type}", kind)
+			.replace("{json}", &json),
+	)
+}
+
+/// Hex encoded HMAC-SHA256 of the body
+pub fn sign(secret: &str, body: &str) -> String {
+	let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac takes any key size");
+	mac.update(body.as_bytes());
+	mac
+		.finalize()
+		.into_bytes()
+		.iter()
+		.map(|byte| format!("{byte:02x}"))
+		.collect()
+}
+
+struct Webhook {
+	client: Client,
+	config: WebhookConfig,
+	headers: HeaderMap,
+}
+
+impl Webhook {
+	fn new(config: WebhookConfig) -> anyhow::Result<Self> {
+		let mut headers = header_map(&config.headers)?;
+		let content_type = config.content_type.as_deref().unwrap_or("application/json");
+		headers.insert(CONTENT_TYPE, HeaderValue::from_str(content_type)?);
+		let client = Client::builder().timeout(config.timeout).build()?;
+		Ok(Self {
+			client,
+			config,
+			headers,
+		})
+	}
+
+	async fn post(&self, event: &Event) -> anyhow::Result<()> {
+		let body = render_body(self.config.template.as_deref(), event)?;
+		let mut headers = self.headers.clone();
+		if let Some(secret) = &self.config.secret {
+			let signature = format!("sha256={}", sign(secret, &body));
+			headers.insert("X-Signature-256", HeaderValue::from_str(&signature)?);
+		}
+		send_with_retry(
+			|| {
+				self
+					.client
+					.post(self.config.url.clone())
+					.headers(headers.clone())
+					.body(body.clone())
+			},
+			self.config.retries,
+			self.config.retry_spacing,
+			self.config.max_retry_spacing,
+		)
+		.await?;
+		debug!(?event, "webhook delivered");
+		Ok(())
+	}
+}
+
+pub struct WebhookController {
+	sender: Option<Sender<Event>>,
+	config: WebhookConfig,
+}
+
+impl WebhookController {
+	pub fn new(config: WebhookConfig) -> Self {
+		Self {
+			sender: None,
+			config,
+		}
+	}
+}
+
+impl EventConsumer for WebhookController {
+	fn inject(&mut self, sender: Sender<Event>) {
+		self.sender = Some(sender)
+	}
+
+	fn start(&self) -> anyhow::Result<()> {
+		if self.sender.is_none() {
+			return Err(anyhow!("There is no sender"));
+		}
+		let mut receiver = self.sender.clone().unwrap().subscribe();
+		let webhook = Webhook::new(self.config.clone())?;
+		let mut cooldown = Cooldown::new(self.config.cooldown);
+
+		tokio::spawn(async move {
+			loop {
+				if let Ok(event) = receiver.recv().await {
+					// the warn before it was posted already
+					if event.is_snapshot() || !cooldown.allow(&event) {
+						continue;
+					}
+					if let Err(e) = webhook.post(&event).await {
+						warn!("webhook delivery failed: {e}");
+					}
+				}
+			}
+		});
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::config::WebhookConfig;
+	use crate::delivery::tests::record_server;
+	use crate::event::Event;
+	use crate::webhook::{Webhook, render_body};
+	use axum::http::StatusCode;
+	use axum::response::IntoResponse;
+	use std::collections::HashMap;
+	use std::time::Duration;
+	use tokio::test;
+
+	#[test]
+	async fn post_with_retry_and_signature() {
+		// the first attempt fails to exercise the retry
+		let (addr, mut records) =
+			record_server(1, || StatusCode::INTERNAL_SERVER_ERROR.into_response()).await;
+
+		let webhook = Webhook::new(WebhookConfig {
+			url: format!("http://{addr}/hook").parse().unwrap(),
+			headers: HashMap::new(),
+			template: Some("{type} {character}".to_string()),
+			content_type: Some("text/plain".to_string()),
+			timeout: Duration::from_secs(5),
+			retries: 2,
+			retry_spacing: Duration::from_millis(10),
+			max_retry_spacing: Duration::from_secs(1),
+			cooldown: Duration::ZERO,
+			secret: Some("secret".to_string()),
+		})
+		.unwrap();
+		let event = Event::Warn {
+			title: "EVE - CHAR1".to_string(),
+		};
+		webhook.post(&event).await.unwrap();
+		let recorded = records.recv().await.unwrap();
+		assert_eq!(recorded.body, "Warn CHAR1");
+		// HMAC-SHA256 of the body keyed with "secret", computed independently
+		assert_eq!(
+			recorded.headers["x-signature-256"],
+			"sha256=a343285a52f969ff7e8176bb4180885f4c71a7449cbb0bee0fc1724b791cc424"
+		);
+
+		assert!(
+			render_body(None, &event)
+				.unwrap()
+				.contains(r#""title":"EVE - CHAR1""#)
+		);
+	}
+}